@@ -1,11 +1,120 @@
 use darling::FromMeta;
+use syn::Path;
 
 #[derive(Debug, Default, FromMeta)]
 pub struct Attributes {
     #[darling(default = "default_cmd")]
-    pub cmd:      String,
+    pub cmd:                 String,
     #[darling(default)]
-    pub no_panic: bool,
+    pub no_panic:            bool,
+    #[darling(default)]
+    pub utf8:                Utf8,
+    #[darling(default)]
+    pub separator:           Separator,
+    pub parse_with:          Option<Path>,
+    pub parse_lines_with:    Option<Path>,
+    #[darling(default)]
+    pub wrap_parse_error:    bool,
+    pub format:              Option<Format>,
+    #[darling(default)]
+    pub no_headers:          bool,
+    pub delimiter:           Option<char>,
+    pub key_value_separator: Option<String>,
+    pub duplicate_keys:      Option<DuplicateKeys>,
+    #[darling(default)]
+    pub collect:             bool,
+    pub returns:             Option<Returns>,
+    #[darling(default)]
+    pub fallible:            bool,
+    #[darling(default)]
+    pub none_codes:          Vec<u32>,
+    #[darling(default)]
+    pub trim:                bool,
+    #[darling(default)]
+    pub skip_empty:          bool,
+    #[darling(default)]
+    pub strip_ansi:          bool,
+    pub skip_comments:       Option<String>,
+    #[darling(default)]
+    pub parse_from:          ParseFrom,
+    pub result_fd:           Option<i32>,
+    #[darling(default)]
+    pub log_bridge:          bool,
+}
+
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, FromMeta)]
+pub enum Utf8 {
+    #[default]
+    #[darling(rename = "strict")]
+    Strict,
+    #[darling(rename = "lossy")]
+    Lossy,
+}
+
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, FromMeta)]
+pub enum ParseFrom {
+    #[default]
+    #[darling(rename = "stdout")]
+    Stdout,
+    #[darling(rename = "stderr")]
+    Stderr,
+    #[darling(rename = "both")]
+    Both,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, FromMeta)]
+pub enum Format {
+    #[darling(rename = "json")]
+    Json,
+    #[darling(rename = "jsonl")]
+    JsonLines,
+    #[darling(rename = "csv")]
+    Csv,
+    #[darling(rename = "tsv")]
+    Tsv,
+    #[darling(rename = "table")]
+    Table,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, FromMeta)]
+pub enum Returns {
+    #[darling(rename = "iter")]
+    Iter,
+    #[darling(rename = "vec")]
+    Vec,
+    #[darling(rename = "value")]
+    Value,
+    #[darling(rename = "unit")]
+    Unit,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, FromMeta)]
+pub enum DuplicateKeys {
+    #[darling(rename = "last")]
+    Last,
+    #[darling(rename = "first")]
+    First,
+    #[darling(rename = "error")]
+    Error,
+}
+
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub enum Separator {
+    #[default]
+    Newline,
+    Byte(u8),
+    Paragraph,
+}
+
+impl FromMeta for Separator {
+    fn from_string(value: &str) -> darling::Result<Self> {
+        match value.as_bytes() {
+            b"\n" => Ok(Separator::Newline),
+            b"paragraph" => Ok(Separator::Paragraph),
+            [byte] => Ok(Separator::Byte(*byte)),
+            _ => Err(darling::Error::unknown_value(value)),
+        }
+    }
 }
 
 fn default_cmd() -> String {
@@ -1,22 +1,74 @@
-use crate::attributes::Attributes;
+use crate::attributes::{Attributes, DuplicateKeys, Format, ParseFrom, Returns, Separator, Utf8};
 use crate::output_type::OutputType;
 use crate::utils::*;
 use proc_macro2::{Ident, Span, TokenStream as TokenStream2};
-use quote::quote;
-use syn::{FnArg, GenericArgument, PathArguments, ReturnType, Type, TypeImplTrait, TypeParamBound};
+use quote::{format_ident, quote};
+use syn::punctuated::Punctuated;
+use syn::{
+    FnArg, GenericArgument, Pat, PatType, Path, PathArguments, ReturnType, Token, Type,
+    TypeParamBound,
+};
 
 const PROGRAM: &str = "PROGRAM";
 
+// `shellfn_log <level> <message>` writes a log record to the fd opened by shellfn-core
+const LOG_PREAMBLE: &str = r#"shellfn_log() { printf '%s\n' "$*" >&"$SHELLFN_LOG_FD"; }
+"#;
+
+// `shellfn_progress <done> <total> [message]` reports progress to the `#[progress]` callback
+const PROGRESS_PREAMBLE: &str = r#"shellfn_progress() { printf '%s\n' "$*" >&"$SHELLFN_PROGRESS_FD"; }
+"#;
+
+// the preambles are shell functions, so they are defined only for scripts run by a POSIX shell, scripts in other
+// languages write to `$SHELLFN_LOG_FD` and `$SHELLFN_PROGRESS_FD` themselves
+const SHELLS: &[&str] = &["sh", "bash", "dash", "ash", "ksh", "mksh", "zsh"];
+
 #[derive(Default)]
 pub struct BlockBuilder {
-    program:      String,
-    cmd:          String,
-    args:         Vec<String>,
-    envs:         Vec<String>,
-    output_type:  OutputType,
-    outer_result: bool,
-    inner_result: bool,
-    no_panic:     bool,
+    name:                String,
+    program:             String,
+    cmd:                 String,
+    args:                Vec<String>,
+    envs:                Vec<String>,
+    output_type:         OutputType,
+    outer_result:        bool,
+    inner_result:        bool,
+    no_panic:            bool,
+    utf8:                Utf8,
+    separator:           Separator,
+    parser:              Option<Parser>,
+    wrap_parse_error:    bool,
+    tuple_item:          bool,
+    delimiter:           Option<char>,
+    map_value:           bool,
+    key_value_separator: Option<String>,
+    duplicate_keys:      Option<DuplicateKeys>,
+    collect:             bool,
+    returns:             Option<Returns>,
+    fallible:            bool,
+    boxed_iter:          Option<Type>,
+    none_codes:          Vec<u32>,
+    trim:                bool,
+    skip_empty:          bool,
+    strip_ansi:          bool,
+    skip_comments:       Option<String>,
+    parse_from:          ParseFrom,
+    result_fd:           Option<i32>,
+    log_bridge:          bool,
+    progress:            Option<Ident>,
+    script_arg:          Option<usize>,
+    secret_envs:         Vec<String>,
+}
+
+enum Parser {
+    Output(Path),
+    Lines(Path),
+    Json,
+    JsonLines,
+    Csv { delimiter: u8, has_headers: bool },
+    Columns,
+    Table,
+    KeyValue,
 }
 
 impl BlockBuilder {
@@ -24,6 +76,11 @@ impl BlockBuilder {
         Self::default()
     }
 
+    pub fn with_name(mut self, name: String) -> Self {
+        self.name = name;
+        self
+    }
+
     pub fn with_program(mut self, program: String) -> Self {
         self.program = program;
         self
@@ -44,6 +101,57 @@ impl BlockBuilder {
         }
 
         self.no_panic = attrs.no_panic;
+        self.utf8 = attrs.utf8;
+        self.separator = attrs.separator;
+        self.parser = match (attrs.parse_with, attrs.parse_lines_with, attrs.format) {
+            (None, None, None) => None,
+            (Some(path), None, None) => Some(Parser::Output(path)),
+            (None, Some(path), None) => Some(Parser::Lines(path)),
+            (None, None, Some(Format::Json)) => Some(Parser::Json),
+            (None, None, Some(Format::JsonLines)) => Some(Parser::JsonLines),
+            (None, None, Some(Format::Csv)) => Some(Parser::Csv {
+                delimiter:   b',',
+                has_headers: !attrs.no_headers,
+            }),
+            (None, None, Some(Format::Tsv)) => Some(Parser::Csv {
+                delimiter:   b'\t',
+                has_headers: !attrs.no_headers,
+            }),
+            (None, None, Some(Format::Table)) => Some(Parser::Table),
+            _ => panic!("only one of parse_with, parse_lines_with and format can be used"),
+        };
+
+        if attrs.no_headers && !matches!(self.parser, Some(Parser::Csv { .. })) {
+            panic!("no_headers can only be used with format = \"csv\" or format = \"tsv\"")
+        }
+
+        self.wrap_parse_error = attrs.wrap_parse_error;
+        self.delimiter = attrs.delimiter;
+        self.key_value_separator = attrs.key_value_separator;
+        self.duplicate_keys = attrs.duplicate_keys;
+        self.collect = attrs.collect;
+        self.returns = attrs.returns;
+        self.fallible = attrs.fallible;
+        self.none_codes = attrs.none_codes;
+        self.trim = attrs.trim;
+        self.skip_empty = attrs.skip_empty;
+        self.strip_ansi = attrs.strip_ansi;
+        self.skip_comments = attrs.skip_comments;
+        self.parse_from = attrs.parse_from;
+        self.result_fd = attrs.result_fd;
+        self.log_bridge = attrs.log_bridge;
+
+        if self.result_fd.is_some_and(|fd| fd < 3) {
+            panic!("result_fd must be 3 or greater, lower file descriptors are stdin, stdout and stderr")
+        }
+
+        if self.result_fd.is_some() && self.parse_from != ParseFrom::Stdout {
+            panic!("only one of parse_from and result_fd can be used")
+        }
+
+        if self.fallible && self.returns.is_none() {
+            panic!("fallible can only be used with returns")
+        }
         self
     }
 
@@ -52,6 +160,17 @@ impl BlockBuilder {
         use FnArg::*;
 
         for arg in args {
+            if let Typed(pat_type) = arg {
+                if pat_type.attrs.iter().any(is_progress_attr) {
+                    self.with_progress_arg(pat_type);
+                    continue;
+                }
+
+                if pat_type.attrs.iter().any(is_secret_attr) {
+                    self.with_secret_arg(pat_type);
+                }
+            }
+
             self.envs.push(
                 match arg {
                     Receiver(_) => "self".to_string(),
@@ -66,6 +185,26 @@ impl BlockBuilder {
         self
     }
 
+    fn with_progress_arg(&mut self, pat_type: &PatType) {
+        if self.progress.is_some() {
+            panic!("only one parameter can be marked with #[progress]")
+        }
+
+        match pat_type.pat.as_ref() {
+            Pat::Ident(ref pat_ident) => self.progress = Some(pat_ident.ident.clone()),
+            _ => panic!("#[progress] parameter must be a simple Ident"),
+        }
+    }
+
+    fn with_secret_arg(&mut self, pat_type: &PatType) {
+        match pat_type.pat.as_ref() {
+            Pat::Ident(ref pat_ident) => self
+                .secret_envs
+                .push(pat_ident.ident.to_string().to_uppercase()),
+            _ => panic!("#[secret] parameter must be a simple Ident"),
+        }
+    }
+
     pub fn with_return_type(mut self, return_type: ReturnType) -> Self {
         match return_type {
             ReturnType::Default => {
@@ -81,13 +220,31 @@ impl BlockBuilder {
                         if let Some(arg) = path_args.args.first() {
                             match arg {
                                 GenericArgument::Type(Type::ImplTrait(ref imp)) => {
-                                    self.with_impl_trait(imp)
+                                    self.with_iterator_bounds(&imp.bounds)
+                                }
+                                GenericArgument::Type(ref t) if boxed_trait_bounds(t).is_some() => {
+                                    self.with_boxed_iterator(t)
                                 }
                                 GenericArgument::Type(ref t) if is_unit_type(t) => {
                                     self.with_unit_return_type();
                                 }
-                                GenericArgument::Type(ref t) if is_vec_type(t) => {
-                                    self.with_vec_return_type(t);
+                                GenericArgument::Type(ref t) if self.collect => {
+                                    self.with_collection_return_type(t);
+                                }
+                                GenericArgument::Type(ref t) if is_bytes_type(t) => {
+                                    self.with_bytes_return_type();
+                                }
+                                GenericArgument::Type(ref t) if is_collection_type(t) => {
+                                    self.with_collection_return_type(t);
+                                }
+                                GenericArgument::Type(ref t) if is_tuple_type(t) => {
+                                    self.tuple_item = true;
+                                }
+                                GenericArgument::Type(ref t) if is_map_type(t) => {
+                                    self.map_value = true;
+                                }
+                                GenericArgument::Type(ref t) if is_option_type(t) => {
+                                    self.with_option_return_type(t);
                                 }
                                 _ => {}
                             }
@@ -96,47 +253,101 @@ impl BlockBuilder {
                 }
                 Type::ImplTrait(ref imp) => {
                     self.outer_result = false;
-                    self.with_impl_trait(imp);
+                    self.with_iterator_bounds(&imp.bounds);
                 }
-                ref t if is_vec_type(t) => self.with_vec_return_type(t),
+                ref t if boxed_trait_bounds(t).is_some() => self.with_boxed_iterator(t),
                 ref t if is_unit_type(t) => self.with_unit_return_type(),
+                ref t if self.collect => self.with_collection_return_type(t),
+                ref t if is_bytes_type(t) => self.with_bytes_return_type(),
+                ref t if is_collection_type(t) => self.with_collection_return_type(t),
+                ref t if is_tuple_type(t) => self.tuple_item = true,
+                ref t if is_map_type(t) => self.map_value = true,
+                ref t if is_option_type(t) => self.with_option_return_type(t),
                 Type::Path(_) => {}
-                ref t => panic!("Unsupported return type {:#?}", t),
+                ref t if self.returns.is_none() => panic!("Unsupported return type {:#?}", t),
+                _ => {}
             },
         }
+
+        self.apply_explicit_return_shape();
         self
     }
 
+    // `returns` overrides the shape detected by type names, e.g. for type aliases
+    fn apply_explicit_return_shape(&mut self) {
+        if let Some(returns) = self.returns {
+            self.outer_result = self.fallible;
+            self.output_type = match returns {
+                Returns::Iter => OutputType::Iter,
+                Returns::Vec => OutputType::Vec,
+                Returns::Value => OutputType::T,
+                Returns::Unit => OutputType::Void,
+            };
+        }
+    }
+
     fn with_unit_return_type(&mut self) {
         self.output_type = OutputType::Void;
     }
 
-    fn with_vec_return_type(&mut self, typ: &Type) {
+    fn with_bytes_return_type(&mut self) {
+        self.output_type = OutputType::Bytes;
+    }
+
+    // any `FromIterator` collection, either known by name or marked with the `collect` flag
+    fn with_collection_return_type(&mut self, typ: &Type) {
+        // maps are parsed from key=value lines, with or without the flag
+        if is_map_type(typ) {
+            self.map_value = true;
+            return;
+        }
+
         self.output_type = OutputType::Vec;
 
         if let Type::Path(ref type_path) = typ {
-            let args = &type_path.path.segments.last().unwrap().arguments;
-
-            if let PathArguments::AngleBracketed(path_args) = args {
-                if let Some(GenericArgument::Type(ref t)) = path_args.args.first() {
-                    self.inner_result = is_result_type(t);
-                }
+            if let Some(t) = collection_item_type(type_path) {
+                self.inner_result = is_result_type(t);
+                self.tuple_item = is_tuple_item_type(t);
             }
         }
     }
 
-    fn with_impl_trait(&mut self, imp: &TypeImplTrait) {
-        if let Some(TypeParamBound::Trait(ref bound)) = imp.bounds.first() {
-            if let Some(segment) = bound.path.segments.first() {
-                if segment.ident == "Iterator" {
+    fn with_option_return_type(&mut self, typ: &Type) {
+        self.output_type = OutputType::Option;
+
+        if let Type::Path(ref type_path) = typ {
+            self.tuple_item = first_type_arg(type_path).is_some_and(is_tuple_type);
+        }
+    }
+
+    fn with_boxed_iterator(&mut self, typ: &Type) {
+        self.with_iterator_bounds(boxed_trait_bounds(typ).unwrap());
+
+        if let OutputType::Iter | OutputType::Events = self.output_type {
+            self.boxed_iter = Some(typ.clone());
+        }
+    }
+
+    // `impl Iterator<Item = T>`, `impl IntoIterator<Item = T>` or `dyn Iterator<Item = T>`
+    fn with_iterator_bounds(&mut self, bounds: &Punctuated<TypeParamBound, Token![+]>) {
+        if let Some(TypeParamBound::Trait(ref bound)) = bounds.first() {
+            if let Some(segment) = bound.path.segments.last() {
+                if segment.ident == "Iterator" || segment.ident == "IntoIterator" {
                     self.output_type = OutputType::Iter;
 
                     if let PathArguments::AngleBracketed(ref path_args) = segment.arguments {
                         if let Some(GenericArgument::AssocType(ref binding)) =
                             path_args.args.first()
                         {
-                            if binding.ident == "Item" && is_result_type(&binding.ty) {
-                                self.inner_result = true;
+                            if binding.ident == "Item" {
+                                self.inner_result = is_result_type(&binding.ty);
+
+                                if let Some(t) = shell_event_item_type(&binding.ty) {
+                                    self.output_type = OutputType::Events;
+                                    self.tuple_item = is_tuple_type(t);
+                                } else {
+                                    self.tuple_item = is_tuple_item_type(&binding.ty);
+                                }
                             }
                         }
                     }
@@ -146,15 +357,45 @@ impl BlockBuilder {
     }
 
     pub fn build(mut self) -> TokenStream2 {
+        let shell = self.runs_shell();
+
+        if self.progress.is_some() && shell {
+            self.program.insert_str(0, PROGRESS_PREAMBLE);
+        }
+
+        if self.log_bridge && shell {
+            self.program.insert_str(0, LOG_PREAMBLE);
+        }
+
         if !self.program.is_empty() {
+            self.script_arg = self.args.iter().position(|a| a == PROGRAM);
             self.add_program_to_args();
         } else {
             self.args.retain(|a| a != PROGRAM);
         }
 
-        let execute_fn = self.select_execute_fn();
+        if self.collect && !matches!(self.output_type, OutputType::Vec) && !self.map_value {
+            panic!("collect requires a collection return type")
+        }
+
+        if !self.none_codes.is_empty() && !matches!(self.output_type, OutputType::Option) {
+            panic!("none_codes requires Option return type")
+        }
+
+        if matches!(self.output_type, OutputType::Events) {
+            self.check_events_attrs();
+        }
+
+        self.apply_default_parser();
+        self.apply_parser_to_output_type();
+
+        let parser = self.build_parser();
+        let execute_fn = self.select_execute_fn(parser.is_some());
+        let options = self.build_options();
+        let cmd = &self.cmd;
+        let call =
+            self.box_iter(quote! { shellfn::#execute_fn(#cmd, args, envs #options #parser) });
         let envs = self.envs;
-        let cmd = self.cmd;
         let env_names = envs.iter().map(|s| s.to_uppercase()).collect::<Vec<_>>();
         let env_vals = envs
             .iter()
@@ -203,10 +444,282 @@ impl BlockBuilder {
             let envs: Vec<(&str, String)> = vec![#((#env_names, #env_vals.to_string())),*];
             let args: Vec<String> = vec![#(#args),*];
 
-            #execute_fn(#cmd, args, envs)
+            #call
         } }
     }
 
+    // both streams are always read line by line and a failed exit is reported with `ShellEvent::Exit`
+    fn check_events_attrs(&self) {
+        if self.separator != Separator::Newline {
+            panic!("separator cannot be used with ShellEvent iterators")
+        }
+
+        if self.parse_from != ParseFrom::Stdout || self.result_fd.is_some() {
+            panic!("parse_from and result_fd cannot be used with ShellEvent iterators")
+        }
+
+        if self.no_panic {
+            panic!("no_panic cannot be used with ShellEvent iterators")
+        }
+    }
+
+    // tuples and maps do not implement FromStr, so they have their own parsers unless one is given:
+    // tuples are parsed column by column, maps from key=value lines
+    fn apply_default_parser(&mut self) {
+        if self.parser.is_none() {
+            if self.tuple_item {
+                self.parser = Some(Parser::Columns);
+            } else if self.map_value {
+                self.parser = Some(Parser::KeyValue);
+            }
+        }
+
+        if self.delimiter.is_some() && !matches!(self.parser, Some(Parser::Columns)) {
+            panic!("delimiter can only be used with tuple return types")
+        }
+
+        if (self.key_value_separator.is_some() || self.duplicate_keys.is_some())
+            && !matches!(self.parser, Some(Parser::KeyValue))
+        {
+            panic!("key_value_separator and duplicate_keys can only be used with map return types")
+        }
+    }
+
+    // parse_with and format = "json" parse the whole output, so e.g. Vec<T> is a single value then
+    fn apply_parser_to_output_type(&mut self) {
+        use OutputType::*;
+
+        match (&self.parser, &self.output_type) {
+            (None, _) => {}
+            (Some(Parser::Output(_)) | Some(Parser::Json), T | Option) => {}
+            (Some(Parser::Output(_)) | Some(Parser::Json), Vec) => self.output_type = T,
+            (Some(Parser::Output(_)), Iter) => {
+                panic!("parse_with parses the whole output, use parse_lines_with for iterators")
+            }
+            (Some(Parser::Json), Iter | Events) => {
+                panic!("format = \"json\" parses the whole output, use format = \"jsonl\" for iterators")
+            }
+            (Some(Parser::Output(_)), Events) => {
+                panic!("parse_with parses the whole output, use parse_lines_with for ShellEvent iterators")
+            }
+            (
+                Some(Parser::Lines(_))
+                | Some(Parser::JsonLines)
+                | Some(Parser::Csv { .. })
+                | Some(Parser::Table),
+                Iter | Vec,
+            ) => {}
+            (Some(Parser::Lines(_)) | Some(Parser::JsonLines), Events) => {}
+            (Some(Parser::Csv { .. }) | Some(Parser::Table), Events) => {
+                panic!("format = \"csv\", format = \"tsv\" and format = \"table\" cannot be used with ShellEvent iterators")
+            }
+            (Some(Parser::Lines(_)), T | Option) => {
+                panic!("parse_lines_with requires iterator or vector return type, use parse_with instead")
+            }
+            (Some(Parser::JsonLines), T | Option) => {
+                panic!("format = \"jsonl\" requires iterator or vector return type, use format = \"json\" instead")
+            }
+            (Some(Parser::Columns), T | Option | Iter | Vec | Events)
+            | (Some(Parser::KeyValue), _) => {}
+            (Some(Parser::Table), T | Option) => {
+                panic!("format = \"table\" requires iterator or vector return type")
+            }
+            (Some(Parser::Csv { .. }), T | Option) => {
+                panic!(
+                    "format = \"csv\" and format = \"tsv\" require iterator or vector return type"
+                )
+            }
+            (Some(_), Void | Bytes) => {
+                panic!("parse_with, parse_lines_with and format require a value to parse")
+            }
+        }
+    }
+
+    // the parser is passed only to `_with` variants of execute fns
+    fn build_parser(&self) -> Option<TokenStream2> {
+        let path = match self.parser {
+            Some(Parser::Output(ref path)) | Some(Parser::Lines(ref path)) => quote! { #path },
+            Some(Parser::Json)
+            | Some(Parser::JsonLines)
+            | Some(Parser::Csv { .. })
+            | Some(Parser::Table)
+                if self.wrap_parse_error =>
+            {
+                panic!("wrap_parse_error cannot be used with format")
+            }
+            Some(Parser::Json) => return Some(quote! { , shellfn::Json }),
+            Some(Parser::JsonLines) => return Some(quote! { , shellfn::JsonLines::default() }),
+            Some(Parser::Csv {
+                delimiter,
+                has_headers,
+            }) => {
+                return Some(quote! {
+                    , shellfn::Csv::new().with_delimiter(#delimiter).with_headers(#has_headers)
+                })
+            }
+            Some(Parser::Table) => return Some(quote! { , shellfn::Table }),
+            Some(Parser::Columns) | Some(Parser::KeyValue) if self.wrap_parse_error => {
+                panic!("wrap_parse_error cannot be used with tuple and map return types")
+            }
+            Some(Parser::KeyValue) => return Some(self.build_key_value_parser()),
+            Some(Parser::Columns) => {
+                return Some(match self.delimiter {
+                    Some(delimiter) => {
+                        quote! { , shellfn::Columns::new().with_delimiter(#delimiter) }
+                    }
+                    None => quote! { , shellfn::Columns::new() },
+                })
+            }
+            None if self.wrap_parse_error && self.parses_output() => {
+                quote! { ::std::str::FromStr::from_str }
+            }
+            None => return None,
+        };
+
+        if self.wrap_parse_error {
+            Some(quote! { , |input: &str| #path(input).map_err(shellfn::DebugError) })
+        } else {
+            Some(quote! { , #path })
+        }
+    }
+
+    fn runs_shell(&self) -> bool {
+        let interpreter = self.cmd.rsplit('/').next().unwrap_or_default();
+
+        SHELLS.contains(&interpreter)
+    }
+
+    fn build_key_value_parser(&self) -> TokenStream2 {
+        let mut parser = quote! { shellfn::KeyValue::new() };
+
+        if let Some(ref separator) = self.key_value_separator {
+            parser = quote! { #parser.with_separator(#separator) };
+        }
+
+        match self.duplicate_keys {
+            None | Some(DuplicateKeys::Last) => {}
+            Some(DuplicateKeys::First) => {
+                parser = quote! { #parser.with_duplicates(shellfn::DuplicateKeys::First) };
+            }
+            Some(DuplicateKeys::Error) => {
+                parser = quote! { #parser.with_duplicates(shellfn::DuplicateKeys::Error) };
+            }
+        }
+
+        quote! { , #parser }
+    }
+
+    // execute fns return `impl Iterator`, so it is boxed for `Box<dyn Iterator<Item = T>>`
+    fn box_iter(&self, call: TokenStream2) -> TokenStream2 {
+        match self.boxed_iter {
+            Some(ref boxed) if self.outer_result => {
+                quote! { #call.map(|iter| -> #boxed { Box::new(iter) }) }
+            }
+            Some(_) => quote! { Box::new(#call) },
+            None => call,
+        }
+    }
+
+    fn parses_output(&self) -> bool {
+        !matches!(self.output_type, OutputType::Void | OutputType::Bytes)
+    }
+
+    // output options are used only by execute fns that read the output as text
+    fn build_options(&self) -> TokenStream2 {
+        if !self.parses_output() {
+            if self.trim || self.skip_empty || self.strip_ansi || self.skip_comments.is_some() {
+                panic!("trim, skip_empty, strip_ansi and skip_comments require a value to parse")
+            }
+
+            if self.parse_from != ParseFrom::Stdout || self.result_fd.is_some() {
+                panic!("parse_from and result_fd require a value to parse")
+            }
+        }
+
+        let mut options = quote! { shellfn::Options::new() };
+
+        if self.utf8 == Utf8::Lossy {
+            options = quote! { #options.with_utf8(shellfn::Utf8::Lossy) };
+        }
+
+        if !self.none_codes.is_empty() {
+            let none_codes = self.none_codes.iter().map(|&code| code as i32);
+            options = quote! { #options.with_none_codes(vec![#(#none_codes),*]) };
+        }
+
+        if self.trim {
+            options = quote! { #options.with_trim(true) };
+        }
+
+        if self.skip_empty {
+            options = quote! { #options.with_skip_empty(true) };
+        }
+
+        if self.strip_ansi {
+            options = quote! { #options.with_strip_ansi(true) };
+        }
+
+        if let Some(ref prefix) = self.skip_comments {
+            options = quote! { #options.with_skip_comments(#prefix) };
+        }
+
+        match self.parse_from {
+            ParseFrom::Stdout => {}
+            ParseFrom::Stderr => {
+                options = quote! { #options.with_parse_from(shellfn::ParseFrom::Stderr) };
+            }
+            ParseFrom::Both => {
+                options = quote! { #options.with_parse_from(shellfn::ParseFrom::Both) };
+            }
+        }
+
+        if let Some(fd) = self.result_fd {
+            options = quote! { #options.with_parse_from(shellfn::ParseFrom::Fd(#fd)) };
+        }
+
+        if let Some(ref progress) = self.progress {
+            options = quote! { #options.with_progress(#progress) };
+        }
+
+        let name = &self.name;
+        options = quote! { #options.with_name(concat!(module_path!(), "::", #name)) };
+
+        if let Some(index) = self.script_arg {
+            options = quote! { #options.with_script_arg(#index) };
+        }
+
+        if !self.secret_envs.is_empty() {
+            let secret_envs = &self.secret_envs;
+            options = quote! { #options.with_secret_envs([#(#secret_envs),*]) };
+        }
+
+        if self.log_bridge {
+            options =
+                quote! { shellfn::__log_target!(#options, concat!(module_path!(), "::", #name)) };
+        }
+
+        match self.separator {
+            Separator::Newline => {}
+            Separator::Byte(byte) => {
+                options = quote! { #options.with_separator(shellfn::Separator::Byte(#byte)) };
+            }
+            Separator::Paragraph => {
+                options = quote! { #options.with_separator(shellfn::Separator::Paragraph) };
+            }
+        }
+
+        // the span is opened by a macro from shellfn-core, so it depends on its `tracing` feature
+        let cmd = &self.cmd;
+        let env_names = self
+            .envs
+            .iter()
+            .map(|s| s.to_uppercase())
+            .collect::<Vec<_>>()
+            .join(",");
+
+        quote! { , shellfn::__trace!(#options, #name, #cmd, #env_names) }
+    }
+
     fn add_program_to_args(&mut self) {
         for arg in self.args.iter_mut() {
             if arg == PROGRAM {
@@ -216,7 +729,7 @@ impl BlockBuilder {
     }
 
     #[rustfmt::skip]
-    fn select_execute_fn(&self) -> TokenStream2 {
+    fn select_execute_fn(&self, with_parser: bool) -> Ident {
         use OutputType::*;
 
         const ORES:    bool = true; // outer result, like Result<impl Iterator<Item=T>, E>
@@ -226,31 +739,45 @@ impl BlockBuilder {
         const NOPANIC: bool = true;
         const PANIC:   bool = false;
 
-        match (
+        let name = match (
             &self.output_type,
             self.outer_result,
             self.inner_result,
             self.no_panic,
         ) {
-            (Void, NOORES, _,      NOPANIC) => quote! { shellfn::execute_void_nopanic },
-            (Void, NOORES, _,      PANIC)   => quote! { shellfn::execute_void_panic },
-            (Void, ORES,   _,      _)       => quote! { shellfn::execute_void_result },
-            (T,    ORES,   _,      _)       => quote! { shellfn::execute_parse_result },
-            (T,    NOORES, _,      _)       => quote! { shellfn::execute_parse_panic },
-            (Iter, ORES,   IRES,   _)       => quote! { shellfn::execute_iter_result_result },
-            (Iter, ORES,   NOIRES, NOPANIC) => quote! { shellfn::execute_iter_result_nopanic },
-            (Iter, ORES,   NOIRES, PANIC)   => quote! { shellfn::execute_iter_result_panic },
-            (Iter, NOORES, IRES,   PANIC)   => quote! { shellfn::execute_iter_panic_result },
-            (Iter, NOORES, IRES,   NOPANIC) => quote! { shellfn::execute_iter_nopanic_result },
-            (Iter, NOORES, NOIRES, NOPANIC) => quote! { shellfn::execute_iter_nopanic_nopanic },
-            (Iter, NOORES, NOIRES, PANIC)   => quote! { shellfn::execute_iter_panic_panic },
-            (Vec,  ORES,   IRES,   _)       => quote! { shellfn::execute_vec_result_result },
-            (Vec,  ORES,   NOIRES, NOPANIC) => quote! { shellfn::execute_vec_result_nopanic },
-            (Vec,  ORES,   NOIRES, PANIC)   => quote! { shellfn::execute_vec_result_panic },
-            (Vec,  NOORES, IRES,   PANIC)   => quote! { shellfn::execute_vec_panic_result },
-            (Vec,  NOORES, IRES,   NOPANIC) => quote! { shellfn::execute_vec_nopanic_result },
-            (Vec,  NOORES, NOIRES, NOPANIC) => quote! { shellfn::execute_vec_nopanic_nopanic },
-            (Vec,  NOORES, NOIRES, PANIC)   => quote! { shellfn::execute_vec_panic_panic },
+            (Void,   NOORES, _,      NOPANIC) => "execute_void_nopanic",
+            (Void,   NOORES, _,      PANIC)   => "execute_void_panic",
+            (Void,   ORES,   _,      _)       => "execute_void_result",
+            (T,      ORES,   _,      _)       => "execute_parse_result",
+            (T,      NOORES, _,      _)       => "execute_parse_panic",
+            (Option, ORES,   _,      _)       => "execute_option_result",
+            (Option, NOORES, _,      _)       => "execute_option_panic",
+            (Bytes,  ORES,   _,      _)       => "execute_bytes_result",
+            (Bytes,  NOORES, _,      _)       => "execute_bytes_panic",
+            (Iter,   ORES,   IRES,   _)       => "execute_iter_result_result",
+            (Iter,   ORES,   NOIRES, NOPANIC) => "execute_iter_result_nopanic",
+            (Iter,   ORES,   NOIRES, PANIC)   => "execute_iter_result_panic",
+            (Iter,   NOORES, IRES,   PANIC)   => "execute_iter_panic_result",
+            (Iter,   NOORES, IRES,   NOPANIC) => "execute_iter_nopanic_result",
+            (Iter,   NOORES, NOIRES, NOPANIC) => "execute_iter_nopanic_nopanic",
+            (Iter,   NOORES, NOIRES, PANIC)   => "execute_iter_panic_panic",
+            (Events, ORES,   IRES,   _)       => "execute_events_result_result",
+            (Events, ORES,   NOIRES, _)       => "execute_events_result_panic",
+            (Events, NOORES, IRES,   _)       => "execute_events_panic_result",
+            (Events, NOORES, NOIRES, _)       => "execute_events_panic_panic",
+            (Vec,    ORES,   IRES,   _)       => "execute_vec_result_result",
+            (Vec,    ORES,   NOIRES, NOPANIC) => "execute_vec_result_nopanic",
+            (Vec,    ORES,   NOIRES, PANIC)   => "execute_vec_result_panic",
+            (Vec,    NOORES, IRES,   PANIC)   => "execute_vec_panic_result",
+            (Vec,    NOORES, IRES,   NOPANIC) => "execute_vec_nopanic_result",
+            (Vec,    NOORES, NOIRES, NOPANIC) => "execute_vec_nopanic_nopanic",
+            (Vec,    NOORES, NOIRES, PANIC)   => "execute_vec_panic_panic",
+        };
+
+        if with_parser {
+            format_ident!("{}_with", name)
+        } else {
+            format_ident!("{}", name)
         }
     }
 }
@@ -0,0 +1,124 @@
+use darling::{ast, FromDeriveInput, FromField};
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{Generics, Ident, Type};
+
+#[derive(FromDeriveInput)]
+#[darling(attributes(shell_line), supports(struct_named))]
+pub struct ShellLine {
+    ident:     Ident,
+    generics:  Generics,
+    data:      ast::Data<(), LineField>,
+    regex:     Option<String>,
+    #[darling(default)]
+    key_value: bool,
+    delimiter: Option<char>,
+}
+
+#[derive(FromField)]
+#[darling(attributes(shell_line))]
+pub struct LineField {
+    ident:  Option<Ident>,
+    ty:     Type,
+    column: Option<usize>,
+    key:    Option<String>,
+}
+
+enum Mode {
+    Columns,
+    Regex(String),
+    KeyValue,
+}
+
+impl ShellLine {
+    pub fn build(self) -> TokenStream2 {
+        let mode = match (self.regex, self.key_value) {
+            (None, false) => Mode::Columns,
+            (Some(regex), false) => Mode::Regex(regex),
+            (None, true) => Mode::KeyValue,
+            (Some(_), true) => panic!("only one of regex and key_value can be used"),
+        };
+
+        let prepare = match mode {
+            Mode::Columns => match self.delimiter {
+                Some(delimiter) => quote! {
+                    let columns: Vec<&str> = line.split(#delimiter).collect();
+                },
+                None => quote! {
+                    let columns: Vec<&str> = line.split_whitespace().collect();
+                },
+            },
+            _ if self.delimiter.is_some() => {
+                panic!("delimiter can only be used with column-bound fields")
+            }
+            Mode::Regex(ref regex) => quote! {
+                static REGEX: ::std::sync::OnceLock<shellfn::Regex> = ::std::sync::OnceLock::new();
+
+                let captures = REGEX
+                    .get_or_init(|| shellfn::Regex::new(#regex).expect("invalid FromShellLine regex"))
+                    .captures(line)
+                    .ok_or(shellfn::LineError::NoMatch)?;
+            },
+            Mode::KeyValue => quote! {},
+        };
+
+        let fields = self
+            .data
+            .take_struct()
+            .expect("FromShellLine supports only structs")
+            .fields;
+        let field_values = fields.iter().enumerate().map(|(index, field)| {
+            let ident = field
+                .ident
+                .as_ref()
+                .expect("FromShellLine supports only named fields");
+            let ty = &field.ty;
+            let name = ident.to_string();
+            let value = match mode {
+                Mode::Columns if field.key.is_some() => {
+                    panic!("key can only be used with regex or key_value")
+                }
+                Mode::Columns => {
+                    let column = field.column.unwrap_or(index);
+                    quote! { columns.get(#column).copied() }
+                }
+                _ if field.column.is_some() => {
+                    panic!("column can only be used with column-bound fields")
+                }
+                Mode::Regex(_) => {
+                    let key = field.key.clone().unwrap_or_else(|| name.clone());
+                    quote! { captures.name(#key).map(|m| m.as_str()) }
+                }
+                Mode::KeyValue => {
+                    let key = field.key.clone().unwrap_or_else(|| name.clone());
+                    quote! {
+                        line.split_whitespace()
+                            .filter_map(|token| token.split_once('='))
+                            .find(|(key, _)| *key == #key)
+                            .map(|(_, value)| value)
+                    }
+                }
+            };
+
+            quote! { #ident: shellfn::parse_field::<#ty>(#name, #value)? }
+        });
+
+        let ident = self.ident;
+        let (impl_generics, ty_generics, where_clause) = self.generics.split_for_impl();
+
+        quote! {
+            impl #impl_generics ::std::str::FromStr for #ident #ty_generics #where_clause {
+                type Err = shellfn::LineError;
+
+                fn from_str(line: &str) -> Result<Self, Self::Err> {
+                    let line = line.trim_end_matches(&['\r', '\n'][..]);
+                    #prepare
+
+                    Ok(Self {
+                        #(#field_values),*
+                    })
+                }
+            }
+        }
+    }
+}
@@ -3,15 +3,18 @@ extern crate proc_macro;
 
 mod attributes;
 mod block_builder;
+mod from_shell_line;
 mod output_type;
 mod utils;
 
 use crate::attributes::Attributes;
 use crate::block_builder::BlockBuilder;
-use darling::FromMeta;
+use crate::from_shell_line::ShellLine;
+use crate::utils::{is_progress_attr, is_secret_attr};
+use darling::{FromDeriveInput, FromMeta};
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{Expr, ExprLit, Lit, Stmt};
+use syn::{Expr, ExprLit, FnArg, Lit, Stmt};
 
 #[proc_macro_attribute]
 pub fn shell(attr: TokenStream, input: TokenStream) -> TokenStream {
@@ -41,6 +44,7 @@ pub fn shell(attr: TokenStream, input: TokenStream) -> TokenStream {
         let mut result = input.clone();
         let program = program.value();
         let block = BlockBuilder::new()
+            .with_name(input.sig.ident.to_string())
             .with_program(program)
             .with_attrs(attrs)
             .with_args(input.sig.inputs.iter())
@@ -49,6 +53,15 @@ pub fn shell(attr: TokenStream, input: TokenStream) -> TokenStream {
 
         result.block = syn::parse2(block).expect("generated invalid block");
 
+        // `#[progress]` and `#[secret]` are not real attributes, they only mark parameters for the block builder
+        for input in result.sig.inputs.iter_mut() {
+            if let FnArg::Typed(ref mut pat_type) = input {
+                pat_type
+                    .attrs
+                    .retain(|attr| !is_progress_attr(attr) && !is_secret_attr(attr));
+            }
+        }
+
         (quote! {
             #result
         })
@@ -59,3 +72,13 @@ pub fn shell(attr: TokenStream, input: TokenStream) -> TokenStream {
         )
     }
 }
+
+#[proc_macro_derive(FromShellLine, attributes(shell_line))]
+pub fn derive_from_shell_line(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as syn::DeriveInput);
+
+    match ShellLine::from_derive_input(&input) {
+        Ok(shell_line) => shell_line.build().into(),
+        Err(e) => TokenStream::from(e.write_errors()),
+    }
+}
@@ -2,7 +2,10 @@
 pub enum OutputType {
     #[default]
     T,
+    Bytes,
+    Events,
     Iter,
+    Option,
     Vec,
     Void,
 }
@@ -1,4 +1,5 @@
-use syn::{Type, TypePath};
+use syn::punctuated::Punctuated;
+use syn::{Attribute, GenericArgument, PathArguments, Token, Type, TypeParamBound, TypePath};
 
 pub fn is_result_type(typ: &Type) -> bool {
     if let Type::Path(ref type_path) = *typ {
@@ -8,6 +9,18 @@ pub fn is_result_type(typ: &Type) -> bool {
     }
 }
 
+pub fn is_bytes_type(typ: &Type) -> bool {
+    if let Type::Path(ref type_path) = *typ {
+        is_path_to("Bytes", type_path)
+            || (is_vec_type_path(type_path)
+                && first_type_arg(type_path).is_some_and(
+                    |t| matches!(t, Type::Path(ref item_path) if item_path.path.is_ident("u8")),
+                ))
+    } else {
+        false
+    }
+}
+
 pub fn is_unit_type(typ: &Type) -> bool {
     if let Type::Tuple(ref tuple) = typ {
         return tuple.elems.is_empty();
@@ -16,14 +29,94 @@ pub fn is_unit_type(typ: &Type) -> bool {
     false
 }
 
-pub fn is_vec_type(typ: &Type) -> bool {
+pub fn is_tuple_type(typ: &Type) -> bool {
+    if let Type::Tuple(ref tuple) = typ {
+        return !tuple.elems.is_empty();
+    }
+
+    false
+}
+
+// tuple item, either `(A, B)` or `Result<(A, B), E>`
+pub fn is_tuple_item_type(typ: &Type) -> bool {
+    match *typ {
+        Type::Path(ref type_path) if is_result_type_path(type_path) => {
+            first_type_arg(type_path).is_some_and(is_tuple_type)
+        }
+        ref t => is_tuple_type(t),
+    }
+}
+
+// `T` of `ShellEvent<T>` or `Result<ShellEvent<T>, E>`
+pub fn shell_event_item_type(typ: &Type) -> Option<&Type> {
+    match *typ {
+        Type::Path(ref type_path) if is_result_type_path(type_path) => {
+            first_type_arg(type_path).and_then(shell_event_item_type)
+        }
+        Type::Path(ref type_path) if is_path_to("ShellEvent", type_path) => {
+            first_type_arg(type_path)
+        }
+        _ => None,
+    }
+}
+
+pub fn is_option_type(typ: &Type) -> bool {
     if let Type::Path(ref type_path) = *typ {
-        is_vec_type_path(type_path)
+        is_path_to("Option", type_path)
     } else {
         false
     }
 }
 
+pub fn is_map_type(typ: &Type) -> bool {
+    if let Type::Path(ref type_path) = *typ {
+        is_path_to("HashMap", type_path)
+            || is_path_to("BTreeMap", type_path)
+            || is_path_to("IndexMap", type_path)
+    } else {
+        false
+    }
+}
+
+const COLLECTIONS: &[&str] = &[
+    "Vec",
+    "VecDeque",
+    "LinkedList",
+    "HashSet",
+    "BTreeSet",
+    "BinaryHeap",
+    "SmallVec",
+];
+
+pub fn is_collection_type(typ: &Type) -> bool {
+    if let Type::Path(ref type_path) = *typ {
+        COLLECTIONS.iter().any(|name| is_path_to(name, type_path))
+    } else {
+        false
+    }
+}
+
+// item type of a collection, e.g. `T` of `Vec<T>` or `SmallVec<[T; 4]>`
+pub fn collection_item_type(type_path: &TypePath) -> Option<&Type> {
+    match first_type_arg(type_path)? {
+        Type::Array(ref array) => Some(&array.elem),
+        t => Some(t),
+    }
+}
+
+// bounds of `Box<dyn Iterator<Item = T>>`
+pub fn boxed_trait_bounds(typ: &Type) -> Option<&Punctuated<TypeParamBound, Token![+]>> {
+    match *typ {
+        Type::Path(ref type_path) if is_path_to("Box", type_path) => {
+            match first_type_arg(type_path)? {
+                Type::TraitObject(ref trait_object) => Some(&trait_object.bounds),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
 pub fn is_result_type_path(type_path: &TypePath) -> bool {
     is_path_to("Result", type_path)
 }
@@ -32,6 +125,17 @@ pub fn is_vec_type_path(type_path: &TypePath) -> bool {
     is_path_to("Vec", type_path)
 }
 
+pub fn first_type_arg(type_path: &TypePath) -> Option<&Type> {
+    if let PathArguments::AngleBracketed(ref path_args) = type_path.path.segments.last()?.arguments
+    {
+        if let Some(GenericArgument::Type(ref t)) = path_args.args.first() {
+            return Some(t);
+        }
+    }
+
+    None
+}
+
 fn is_path_to(name: &str, type_path: &TypePath) -> bool {
     type_path
         .path
@@ -39,3 +143,13 @@ fn is_path_to(name: &str, type_path: &TypePath) -> bool {
         .last()
         .is_some_and(|s| s.ident == name)
 }
+
+// `#[progress]` marks the progress callback parameter
+pub fn is_progress_attr(attr: &Attribute) -> bool {
+    attr.path().is_ident("progress")
+}
+
+// `#[secret]` marks parameters whose values are redacted in diagnostics
+pub fn is_secret_attr(attr: &Attribute) -> bool {
+    attr.path().is_ident("secret")
+}
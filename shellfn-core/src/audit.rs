@@ -0,0 +1,129 @@
+use crate::hooks::{Invocation, Outcome};
+use crate::options::Options;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{OnceLock, RwLock};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Path of the JSON Lines file every invocation is appended to, unless `set_audit_log` is called
+pub const AUDIT_LOG_ENV: &str = "SHELLFN_AUDIT_LOG";
+
+static AUDIT_LOG: OnceLock<RwLock<Option<PathBuf>>> = OnceLock::new();
+
+/// Appends a JSON object for every following invocation to the file, or stops auditing with `None`
+///
+/// Overrides the `SHELLFN_AUDIT_LOG` environment variable
+pub fn set_audit_log(path: Option<impl Into<PathBuf>>) {
+    *audit_log().write().unwrap() = path.map(Into::into);
+}
+
+fn audit_log() -> &'static RwLock<Option<PathBuf>> {
+    AUDIT_LOG.get_or_init(|| RwLock::new(env::var_os(AUDIT_LOG_ENV).map(PathBuf::from)))
+}
+
+/// Start of an invocation, written to the audit log once it ends
+#[derive(Clone)]
+pub struct Audit {
+    timestamp:  SystemTime,
+    started:    Instant,
+    script_arg: Option<usize>,
+}
+
+impl Audit {
+    pub fn new(options: &Options) -> Self {
+        Self {
+            timestamp:  SystemTime::now(),
+            started:    Instant::now(),
+            script_arg: options.script_arg(),
+        }
+    }
+
+    /// Appends the record of the invocation, errors are ignored so auditing never fails the call
+    pub fn record(&self, invocation: &Invocation, outcome: &Outcome) {
+        if let Some(ref path) = *audit_log().read().unwrap() {
+            let _ = append(path, &self.to_json(invocation, outcome));
+        }
+    }
+
+    fn to_json(&self, invocation: &Invocation, outcome: &Outcome) -> Value {
+        let secrets = invocation.secrets();
+        let argv = std::iter::once(invocation.cmd().to_string_lossy().into_owned())
+            .chain(invocation.args().iter().enumerate().map(|(i, arg)| {
+                if Some(i) == self.script_arg {
+                    format!("sha256:{:x}", Sha256::digest(arg.as_encoded_bytes()))
+                } else {
+                    secrets.redact_str(&arg.to_string_lossy()).into_owned()
+                }
+            }))
+            .collect::<Vec<_>>();
+        let envs = invocation
+            .envs()
+            .iter()
+            .map(|(key, _)| key.to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        let (exit_code, error) = match outcome {
+            Outcome::Exited(status) => (status.code(), None),
+            Outcome::SpawnFailed(e) => (None, Some(e.to_string())),
+        };
+
+        json!({
+            "timestamp": timestamp(self.timestamp),
+            "function": invocation.name(),
+            "interpreter": invocation.cmd().to_string_lossy(),
+            "argv": argv,
+            "envs": envs,
+            "cwd": env::current_dir().ok(),
+            "user": env::var("USER").or_else(|_| env::var("LOGNAME")).ok(),
+            "exit_code": exit_code,
+            "error": error,
+            "duration_ms": self.started.elapsed().as_millis() as u64,
+        })
+    }
+}
+
+// a single write of the whole line, so records of concurrent invocations are not interleaved
+fn append(path: &Path, record: &Value) -> std::io::Result<()> {
+    let mut line = record.to_string();
+    line.push('\n');
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(line.as_bytes())
+}
+
+// RFC 3339 in UTC with milliseconds, days are converted to a date with Howard Hinnant's `civil_from_days`
+fn timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let days = (secs / 86_400) as i64 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs % 86_400 / 3600,
+        secs % 3600 / 60,
+        secs % 60,
+        since_epoch.subsec_millis()
+    )
+}
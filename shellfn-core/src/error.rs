@@ -1,12 +1,13 @@
-use std::error::Error as StdError;
+use std::fmt::{self, Debug, Display};
 use std::io;
 use std::process::Output;
 use std::string::FromUtf8Error;
 
 #[derive(thiserror::Error, Debug)]
-pub enum Error<PE: StdError> {
+pub enum Error<PE> {
     #[error("subprocess stdout contains non-utf8 characters")]
     NonUtf8Stdout(#[source] FromUtf8Error),
+    /// Not redacted, the error of the parser may quote the output, including secrets printed by the script
     #[error("could not parse subprocess output")]
     ParsingError(#[source] PE),
     #[error("could not spawn subprocess")]
@@ -17,8 +18,106 @@ pub enum Error<PE: StdError> {
     WaitFailed(#[source] io::Error),
     #[error("subprocess finished with error")]
     ProcessFailed(Output),
+    /// Reported with the `serde` feature, the source is `serde_json::Error`. Not gated behind the feature, so
+    /// exhaustive matches compile with any set of features
+    #[error("could not parse subprocess output as JSON at line {line}, column {column}")]
+    JsonParsingError {
+        line:   usize,
+        column: usize,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+}
+
+impl<PE> Error<PE> {
+    /// Whether the output was read, but could not be parsed
+    pub fn is_parsing_error(&self) -> bool {
+        matches!(
+            self,
+            Error::ParsingError(_) | Error::JsonParsingError { .. }
+        )
+    }
 }
 
 // TODO: replace with `!` after stabilization
 #[derive(thiserror::Error, Debug, Copy, Clone, Eq, PartialEq)]
 pub enum NeverError {}
+
+/// Error of splitting a record into columns, with the index of the column (counted from 0)
+#[derive(thiserror::Error, Debug)]
+pub enum ColumnError {
+    #[error("missing column {column}")]
+    Missing { column: usize },
+    #[error("unexpected column {column}")]
+    Unexpected { column: usize },
+    #[error("could not parse column {column}")]
+    Invalid {
+        column: usize,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+}
+
+/// Error of the `FromStr` implementation generated by `#[derive(FromShellLine)]`
+#[derive(thiserror::Error, Debug)]
+pub enum LineError {
+    #[error("line does not match the pattern")]
+    NoMatch,
+    #[error("missing field {field}")]
+    MissingField { field: &'static str },
+    #[error("could not parse field {field}")]
+    InvalidField {
+        field:  &'static str,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+}
+
+/// Error of parsing `key=value` lines into a map
+#[derive(thiserror::Error, Debug)]
+pub enum KeyValueError {
+    #[error("missing key-value separator in line {line:?}")]
+    MissingSeparator { line: String },
+    #[error("could not parse key {key:?}")]
+    InvalidKey {
+        key:    String,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    #[error("could not parse value of key {key:?}")]
+    InvalidValue {
+        key:    String,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    #[error("duplicate key {key:?}")]
+    DuplicateKey { key: String },
+}
+
+/// Error of parsing a column-aligned table, with the number of the row (counted from 1, without the header)
+#[cfg(feature = "csv")]
+#[derive(thiserror::Error, Debug)]
+pub enum TableError {
+    #[error("table has no rows")]
+    NoRows,
+    #[error("could not parse table row {row}")]
+    InvalidRow {
+        row:    usize,
+        #[source]
+        source: csv::Error,
+    },
+}
+
+/// Adapter for parsing errors that do not implement `std::error::Error`, e.g. `String` or `()`
+///
+/// The wrapped error is displayed using its `Debug` implementation
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct DebugError<E>(pub E);
+
+impl<E: Debug> Display for DebugError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.0)
+    }
+}
+
+impl<E: Debug> std::error::Error for DebugError<E> {}
@@ -0,0 +1,12 @@
+use std::process::ExitStatus;
+
+/// Item of iterators over both streams of the subprocess, yielded in the order the lines are written
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ShellEvent<T> {
+    /// Parsed line of stdout
+    Stdout(T),
+    /// Line of stderr, decoded lossily and without the line ending
+    Stderr(String),
+    /// Exit status of the subprocess, always the last event
+    Exit(ExitStatus),
+}
@@ -0,0 +1,326 @@
+use crate::error::Error;
+use crate::event::ShellEvent;
+use crate::metrics::counted;
+use crate::options::{Options, ParseFrom};
+use crate::parser::Parser;
+use crate::utils::{decode, merged_lines, normalize, spawn, Process, Stream, PANIC_MSG};
+use std::ffi::OsStr;
+use std::fmt::Debug;
+use std::io;
+use std::iter;
+use std::str::FromStr;
+
+/// Executes command with args and environment variables, reads stdout and stderr concurrently and yields their lines
+/// in the order they are written, parsing stdout line by line, followed by the exit status
+/// * On invalid command: return error
+/// * On error exit code: yield `ShellEvent::Exit` with the status
+/// * On parsing failure: yield error item
+/// * Possible errors: ProcessNotSpawned, StdoutUnreadable (item error, also for stderr), NonUtf8Stdout (item error),
+///   ParsingError (item error), WaitFailed (item error)
+///
+/// Designed for
+/// ```rust
+/// use shellfn::{shell, ShellEvent};
+/// use std::error::Error;
+///
+/// #[shell]
+/// fn command() -> Result<impl Iterator<Item = Result<ShellEvent<u32>, Box<Error + 'static>>>, Box<Error>> {
+///     "echo 1; echo warning >&2; echo 2"
+/// }
+///
+/// for event in command().unwrap() {
+///     println!("{:?}", event.unwrap())
+/// }
+/// ```
+pub fn execute_events_result_result<T, TArg, TEnvKey, TEnvVal, TOuterError, TInnerError>(
+    cmd: impl AsRef<OsStr>,
+    args: impl IntoIterator<Item = TArg>,
+    envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    options: Options,
+) -> Result<impl Iterator<Item = Result<ShellEvent<T>, TInnerError>>, TOuterError>
+where
+    T: FromStr,
+    TArg: AsRef<OsStr>,
+    TEnvKey: AsRef<OsStr>,
+    TEnvVal: AsRef<OsStr>,
+    TOuterError: From<Error<<T as FromStr>::Err>>,
+    TInnerError: From<Error<<T as FromStr>::Err>>,
+{
+    execute_events_result_result_with(cmd, args, envs, options, str::parse::<T>)
+}
+
+/// Same as `execute_events_result_result`, but parses every stdout line with the given parser instead of `FromStr`
+pub fn execute_events_result_result_with<
+    T,
+    TParser,
+    TArg,
+    TEnvKey,
+    TEnvVal,
+    TOuterError,
+    TInnerError,
+>(
+    cmd: impl AsRef<OsStr>,
+    args: impl IntoIterator<Item = TArg>,
+    envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    options: Options,
+    parser: TParser,
+) -> Result<impl Iterator<Item = Result<ShellEvent<T>, TInnerError>>, TOuterError>
+where
+    TParser: Parser<T>,
+    TArg: AsRef<OsStr>,
+    TEnvKey: AsRef<OsStr>,
+    TEnvVal: AsRef<OsStr>,
+    TOuterError: From<Error<TParser::Error>>,
+    TInnerError: From<Error<TParser::Error>>,
+{
+    let parser = counted(parser, &options);
+    let mut options = options.with_parse_from(ParseFrom::Both);
+    let process = spawn(cmd, args, envs, &mut options).map_err(Error::ProcessNotSpawned)?;
+
+    Ok(events(process, options, parser).map(|event| event.map_err(Into::into)))
+}
+
+/// Executes command with args and environment variables, reads stdout and stderr concurrently and yields their lines
+/// in the order they are written, parsing stdout line by line, followed by the exit status
+/// * On invalid command: return error
+/// * On error exit code: yield `ShellEvent::Exit` with the status
+/// * On parsing failure: panic
+/// * Possible errors: ProcessNotSpawned
+///
+/// Designed for
+/// ```rust
+/// use shellfn::{shell, ShellEvent};
+/// use std::error::Error;
+///
+/// #[shell]
+/// fn command() -> Result<impl Iterator<Item = ShellEvent<u32>>, Box<Error>> {
+///     "echo 1; echo warning >&2; echo 2"
+/// }
+///
+/// for event in command().unwrap() {
+///     println!("{:?}", event)
+/// }
+/// ```
+pub fn execute_events_result_panic<T, TArg, TEnvKey, TEnvVal, TError>(
+    cmd: impl AsRef<OsStr>,
+    args: impl IntoIterator<Item = TArg>,
+    envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    options: Options,
+) -> Result<impl Iterator<Item = ShellEvent<T>>, TError>
+where
+    T: FromStr,
+    TArg: AsRef<OsStr>,
+    TEnvKey: AsRef<OsStr>,
+    TEnvVal: AsRef<OsStr>,
+    <T as FromStr>::Err: Debug,
+    TError: From<Error<<T as FromStr>::Err>>,
+{
+    execute_events_result_panic_with(cmd, args, envs, options, str::parse::<T>)
+}
+
+/// Same as `execute_events_result_panic`, but parses every stdout line with the given parser instead of `FromStr`
+pub fn execute_events_result_panic_with<T, TParser, TArg, TEnvKey, TEnvVal, TError>(
+    cmd: impl AsRef<OsStr>,
+    args: impl IntoIterator<Item = TArg>,
+    envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    options: Options,
+    parser: TParser,
+) -> Result<impl Iterator<Item = ShellEvent<T>>, TError>
+where
+    TParser: Parser<T>,
+    TParser::Error: Debug,
+    TArg: AsRef<OsStr>,
+    TEnvKey: AsRef<OsStr>,
+    TEnvVal: AsRef<OsStr>,
+    TError: From<Error<TParser::Error>>,
+{
+    let parser = counted(parser, &options);
+    let mut options = options.with_parse_from(ParseFrom::Both);
+    let process = spawn(cmd, args, envs, &mut options).map_err(Error::ProcessNotSpawned)?;
+
+    Ok(events(process, options, parser).map(|event| event.expect(PANIC_MSG)))
+}
+
+/// Executes command with args and environment variables, reads stdout and stderr concurrently and yields their lines
+/// in the order they are written, parsing stdout line by line, followed by the exit status
+/// * On invalid command: panic
+/// * On error exit code: yield `ShellEvent::Exit` with the status
+/// * On parsing failure: yield error item
+/// * Possible errors: StdoutUnreadable (item error, also for stderr), NonUtf8Stdout (item error),
+///   ParsingError (item error), WaitFailed (item error)
+///
+/// Designed for
+/// ```rust
+/// use shellfn::{shell, ShellEvent};
+/// use std::error::Error;
+///
+/// #[shell]
+/// fn command() -> impl Iterator<Item = Result<ShellEvent<u32>, Box<Error + 'static>>> {
+///     "echo 1; echo warning >&2; echo 2"
+/// }
+///
+/// for event in command() {
+///     println!("{:?}", event.unwrap())
+/// }
+/// ```
+pub fn execute_events_panic_result<T, TArg, TEnvKey, TEnvVal, TError>(
+    cmd: impl AsRef<OsStr>,
+    args: impl IntoIterator<Item = TArg>,
+    envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    options: Options,
+) -> impl Iterator<Item = Result<ShellEvent<T>, TError>>
+where
+    T: FromStr,
+    TArg: AsRef<OsStr>,
+    TEnvKey: AsRef<OsStr>,
+    TEnvVal: AsRef<OsStr>,
+    TError: From<Error<<T as FromStr>::Err>>,
+{
+    execute_events_panic_result_with(cmd, args, envs, options, str::parse::<T>)
+}
+
+/// Same as `execute_events_panic_result`, but parses every stdout line with the given parser instead of `FromStr`
+pub fn execute_events_panic_result_with<T, TParser, TArg, TEnvKey, TEnvVal, TError>(
+    cmd: impl AsRef<OsStr>,
+    args: impl IntoIterator<Item = TArg>,
+    envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    options: Options,
+    parser: TParser,
+) -> impl Iterator<Item = Result<ShellEvent<T>, TError>>
+where
+    TParser: Parser<T>,
+    TArg: AsRef<OsStr>,
+    TEnvKey: AsRef<OsStr>,
+    TEnvVal: AsRef<OsStr>,
+    TError: From<Error<TParser::Error>>,
+{
+    let parser = counted(parser, &options);
+    let mut options = options.with_parse_from(ParseFrom::Both);
+    let process = spawn(cmd, args, envs, &mut options).expect(PANIC_MSG);
+
+    events(process, options, parser).map(|event| event.map_err(Into::into))
+}
+
+/// Executes command with args and environment variables, reads stdout and stderr concurrently and yields their lines
+/// in the order they are written, parsing stdout line by line, followed by the exit status
+/// * On invalid command: panic
+/// * On error exit code: yield `ShellEvent::Exit` with the status
+/// * On parsing failure: panic
+/// * Possible errors: N/A
+///
+/// Designed for
+/// ```rust
+/// use shellfn::{shell, ShellEvent};
+///
+/// #[shell]
+/// fn command() -> impl Iterator<Item = ShellEvent<u32>> {
+///     "echo 1; echo warning >&2; echo 2"
+/// }
+///
+/// for event in command() {
+///     println!("{:?}", event)
+/// }
+/// ```
+pub fn execute_events_panic_panic<T, TArg, TEnvKey, TEnvVal>(
+    cmd: impl AsRef<OsStr>,
+    args: impl IntoIterator<Item = TArg>,
+    envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    options: Options,
+) -> impl Iterator<Item = ShellEvent<T>>
+where
+    T: FromStr,
+    TArg: AsRef<OsStr>,
+    TEnvKey: AsRef<OsStr>,
+    TEnvVal: AsRef<OsStr>,
+    <T as FromStr>::Err: Debug,
+{
+    execute_events_panic_panic_with(cmd, args, envs, options, str::parse::<T>)
+}
+
+/// Same as `execute_events_panic_panic`, but parses every stdout line with the given parser instead of `FromStr`
+pub fn execute_events_panic_panic_with<T, TParser, TArg, TEnvKey, TEnvVal>(
+    cmd: impl AsRef<OsStr>,
+    args: impl IntoIterator<Item = TArg>,
+    envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    options: Options,
+    parser: TParser,
+) -> impl Iterator<Item = ShellEvent<T>>
+where
+    TParser: Parser<T>,
+    TParser::Error: Debug,
+    TArg: AsRef<OsStr>,
+    TEnvKey: AsRef<OsStr>,
+    TEnvVal: AsRef<OsStr>,
+{
+    let parser = counted(parser, &options);
+    let mut options = options.with_parse_from(ParseFrom::Both);
+    let process = spawn(cmd, args, envs, &mut options).expect(PANIC_MSG);
+
+    events(process, options, parser).map(|event| event.expect(PANIC_MSG))
+}
+
+fn events<T, TParser>(
+    mut process: Process,
+    options: Options,
+    mut parser: TParser,
+) -> impl Iterator<Item = Result<ShellEvent<T>, Error<TParser::Error>>>
+where
+    TParser: Parser<T>,
+{
+    let trace = process.trace().clone();
+    let lines = merged_lines(
+        process.stdout.take().unwrap(),
+        process.stderr.take().unwrap(),
+    );
+
+    lines
+        .into_iter()
+        .inspect(move |(stream, line)| {
+            if let (Stream::Stdout, Ok(line)) = (stream, line) {
+                trace.count_lines(line);
+            }
+        })
+        .filter_map(move |(stream, line)| line_event(stream, line, &options, &mut parser))
+        .chain(iter::once_with(move || {
+            process
+                .wait()
+                .map(ShellEvent::Exit)
+                .map_err(Error::WaitFailed)
+        }))
+}
+
+// stdout lines are normalized and skipped like iterator items, stderr lines are passed as they are
+fn line_event<T, TParser>(
+    stream: Stream,
+    line: io::Result<Vec<u8>>,
+    options: &Options,
+    parser: &mut TParser,
+) -> Option<Result<ShellEvent<T>, Error<TParser::Error>>>
+where
+    TParser: Parser<T>,
+{
+    let mut line = match line {
+        Ok(line) => line,
+        Err(e) => return Some(Err(Error::StdoutUnreadable(e))),
+    };
+
+    if line.ends_with(b"\n") {
+        line.pop();
+    }
+
+    if line.ends_with(b"\r") {
+        line.pop();
+    }
+
+    match stream {
+        Stream::Stderr => Some(Ok(ShellEvent::Stderr(
+            String::from_utf8_lossy(&line).into_owned(),
+        ))),
+        Stream::Stdout => match decode(line, options) {
+            Ok(line) => {
+                normalize(line, options).map(|line| parser.parse(&line).map(ShellEvent::Stdout))
+            }
+            Err(e) => Some(Err(Error::NonUtf8Stdout(e))),
+        },
+    }
+}
@@ -1,7 +1,10 @@
-use crate::error::Error;
-use crate::utils::{spawn, PANIC_MSG};
-use std::error::Error as StdError;
+use crate::error::{Error, NeverError};
+use crate::metrics::counted;
+use crate::options::Options;
+use crate::parser::Parser;
+use crate::utils::{decode_output, parsed_output, spawn, wait_with_output, PANIC_MSG};
 use std::ffi::OsStr;
+use std::fmt::Debug;
 use std::str::FromStr;
 
 /// Executes command with args and environment variables, parses output
@@ -26,26 +29,46 @@ pub fn execute_parse_result<T, TArg, TEnvKey, TEnvVal, TError>(
     cmd: impl AsRef<OsStr>,
     args: impl IntoIterator<Item = TArg>,
     envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    options: Options,
 ) -> Result<T, TError>
 where
     T: FromStr,
     TArg: AsRef<OsStr>,
     TEnvKey: AsRef<OsStr>,
     TEnvVal: AsRef<OsStr>,
-    <T as FromStr>::Err: StdError,
     TError: From<Error<<T as FromStr>::Err>>,
 {
-    let process = spawn(cmd, args, envs).map_err(Error::ProcessNotSpawned)?;
-    let result = process.wait_with_output().map_err(Error::WaitFailed)?;
+    execute_parse_result_with(cmd, args, envs, options, str::parse::<T>)
+}
+
+/// Same as `execute_parse_result`, but parses the output with the given parser instead of `FromStr`
+pub fn execute_parse_result_with<T, TParser, TArg, TEnvKey, TEnvVal, TError>(
+    cmd: impl AsRef<OsStr>,
+    args: impl IntoIterator<Item = TArg>,
+    envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    mut options: Options,
+    parser: TParser,
+) -> Result<T, TError>
+where
+    TParser: Parser<T>,
+    TArg: AsRef<OsStr>,
+    TEnvKey: AsRef<OsStr>,
+    TEnvVal: AsRef<OsStr>,
+    TError: From<Error<TParser::Error>>,
+{
+    let mut parser = counted(parser, &options);
+    let parse_from = options.parse_from();
+    let process = spawn(cmd, args, envs, &mut options).map_err(Error::ProcessNotSpawned)?;
+    let result = wait_with_output(process, parse_from).map_err(Error::WaitFailed)?;
 
     if !result.status.success() {
         return Err(Error::ProcessFailed(result).into());
     }
 
-    String::from_utf8(result.stdout)
+    decode_output(parsed_output(result, parse_from), &options)
         .map_err(Error::NonUtf8Stdout)
         .map_err(Into::into)
-        .and_then(|s| s.parse().map_err(Error::ParsingError).map_err(Into::into))
+        .and_then(|s| parser.parse(&s).map_err(Into::into))
 }
 
 /// Executes command with args and environment variables, parses output
@@ -69,25 +92,265 @@ pub fn execute_parse_panic<T, TArg, TEnvKey, TEnvVal>(
     cmd: impl AsRef<OsStr>,
     args: impl IntoIterator<Item = TArg>,
     envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    options: Options,
 ) -> T
 where
     T: FromStr,
     TArg: AsRef<OsStr>,
     TEnvKey: AsRef<OsStr>,
     TEnvVal: AsRef<OsStr>,
-    <T as FromStr>::Err: StdError,
+    <T as FromStr>::Err: Debug,
 {
-    let result = spawn(cmd, args, envs)
-        .expect(PANIC_MSG)
-        .wait_with_output()
-        .expect(PANIC_MSG);
+    execute_parse_panic_with(cmd, args, envs, options, str::parse::<T>)
+}
+
+/// Same as `execute_parse_panic`, but parses the output with the given parser instead of `FromStr`
+pub fn execute_parse_panic_with<T, TParser, TArg, TEnvKey, TEnvVal>(
+    cmd: impl AsRef<OsStr>,
+    args: impl IntoIterator<Item = TArg>,
+    envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    mut options: Options,
+    parser: TParser,
+) -> T
+where
+    TParser: Parser<T>,
+    TParser::Error: Debug,
+    TArg: AsRef<OsStr>,
+    TEnvKey: AsRef<OsStr>,
+    TEnvVal: AsRef<OsStr>,
+{
+    let mut parser = counted(parser, &options);
+    let parse_from = options.parse_from();
+    let process = spawn(cmd, args, envs, &mut options).expect(PANIC_MSG);
+    let result = wait_with_output(process, parse_from).expect(PANIC_MSG);
 
     if !result.status.success() {
         panic!("{}", PANIC_MSG);
     }
 
-    String::from_utf8(result.stdout)
-        .expect(PANIC_MSG)
-        .parse()
+    parser
+        .parse(&decode_output(parsed_output(result, parse_from), &options).expect(PANIC_MSG))
         .expect(PANIC_MSG)
 }
+
+/// Executes command with args and environment variables, parses output unless it is empty
+/// * On invalid command: return error
+/// * On error exit code: return `None` for exit codes listed in `Options::none_codes`, error otherwise
+/// * On empty or whitespace-only output: return `None`
+/// * On parsing failure: return error
+/// * Possible errors: ProcessNotSpawned, WaitFailed, ProcessFailed, NonUtf8Stdout, ParsingError
+///
+/// Designed for
+/// ```rust
+/// use shellfn::shell;
+/// use std::error::Error;
+///
+/// #[shell(none_codes = [1])]
+/// fn command() -> Result<Option<u32>, Box<Error>> {
+///     "grep -o '[0-9]*' <<< 'no digits'"
+/// }
+///
+/// assert_eq!(None, command().unwrap())
+/// ```
+pub fn execute_option_result<T, TArg, TEnvKey, TEnvVal, TError>(
+    cmd: impl AsRef<OsStr>,
+    args: impl IntoIterator<Item = TArg>,
+    envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    options: Options,
+) -> Result<Option<T>, TError>
+where
+    T: FromStr,
+    TArg: AsRef<OsStr>,
+    TEnvKey: AsRef<OsStr>,
+    TEnvVal: AsRef<OsStr>,
+    TError: From<Error<<T as FromStr>::Err>>,
+{
+    execute_option_result_with(cmd, args, envs, options, str::parse::<T>)
+}
+
+/// Same as `execute_option_result`, but parses the output with the given parser instead of `FromStr`
+pub fn execute_option_result_with<T, TParser, TArg, TEnvKey, TEnvVal, TError>(
+    cmd: impl AsRef<OsStr>,
+    args: impl IntoIterator<Item = TArg>,
+    envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    mut options: Options,
+    parser: TParser,
+) -> Result<Option<T>, TError>
+where
+    TParser: Parser<T>,
+    TArg: AsRef<OsStr>,
+    TEnvKey: AsRef<OsStr>,
+    TEnvVal: AsRef<OsStr>,
+    TError: From<Error<TParser::Error>>,
+{
+    let mut parser = counted(parser, &options);
+    let parse_from = options.parse_from();
+    let process = spawn(cmd, args, envs, &mut options).map_err(Error::ProcessNotSpawned)?;
+    let result = wait_with_output(process, parse_from).map_err(Error::WaitFailed)?;
+
+    if !result.status.success() {
+        return match result.status.code() {
+            Some(code) if options.none_codes().contains(&code) => Ok(None),
+            _ => Err(Error::ProcessFailed(result).into()),
+        };
+    }
+
+    let output =
+        decode_output(parsed_output(result, parse_from), &options).map_err(Error::NonUtf8Stdout)?;
+
+    if output.trim().is_empty() {
+        return Ok(None);
+    }
+
+    parser.parse(&output).map(Some).map_err(Into::into)
+}
+
+/// Executes command with args and environment variables, parses output unless it is empty
+/// * On invalid command: panic
+/// * On error exit code: return `None` for exit codes listed in `Options::none_codes`, panic otherwise
+/// * On empty or whitespace-only output: return `None`
+/// * On parsing failure: panic
+/// * Possible errors: N/A
+///
+/// Designed for
+/// ```rust
+/// use shellfn::shell;
+///
+/// #[shell]
+/// fn command() -> Option<u32> {
+///     "echo"
+/// }
+///
+/// assert_eq!(None, command())
+/// ```
+pub fn execute_option_panic<T, TArg, TEnvKey, TEnvVal>(
+    cmd: impl AsRef<OsStr>,
+    args: impl IntoIterator<Item = TArg>,
+    envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    options: Options,
+) -> Option<T>
+where
+    T: FromStr,
+    TArg: AsRef<OsStr>,
+    TEnvKey: AsRef<OsStr>,
+    TEnvVal: AsRef<OsStr>,
+    <T as FromStr>::Err: Debug,
+{
+    execute_option_panic_with(cmd, args, envs, options, str::parse::<T>)
+}
+
+/// Same as `execute_option_panic`, but parses the output with the given parser instead of `FromStr`
+pub fn execute_option_panic_with<T, TParser, TArg, TEnvKey, TEnvVal>(
+    cmd: impl AsRef<OsStr>,
+    args: impl IntoIterator<Item = TArg>,
+    envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    mut options: Options,
+    parser: TParser,
+) -> Option<T>
+where
+    TParser: Parser<T>,
+    TParser::Error: Debug,
+    TArg: AsRef<OsStr>,
+    TEnvKey: AsRef<OsStr>,
+    TEnvVal: AsRef<OsStr>,
+{
+    let mut parser = counted(parser, &options);
+    let parse_from = options.parse_from();
+    let process = spawn(cmd, args, envs, &mut options).expect(PANIC_MSG);
+    let result = wait_with_output(process, parse_from).expect(PANIC_MSG);
+
+    if !result.status.success() {
+        match result.status.code() {
+            Some(code) if options.none_codes().contains(&code) => return None,
+            _ => panic!("{}", PANIC_MSG),
+        }
+    }
+
+    let output = decode_output(parsed_output(result, parse_from), &options).expect(PANIC_MSG);
+
+    if output.trim().is_empty() {
+        return None;
+    }
+
+    Some(parser.parse(&output).expect(PANIC_MSG))
+}
+
+/// Executes command with args and environment variables, returns raw output
+/// * On invalid command: return error
+/// * On error exit code: return error
+/// * On parsing failure: N/A
+/// * Possible errors: ProcessNotSpawned, WaitFailed, ProcessFailed
+///
+/// Designed for
+/// ```rust
+/// use shellfn::shell;
+/// use std::error::Error;
+///
+/// #[shell]
+/// fn command() -> Result<Vec<u8>, Box<Error>> {
+///     "printf '\\x00\\xff'"
+/// }
+///
+/// assert_eq!(vec![0, 255], command().unwrap())
+/// ```
+pub fn execute_bytes_result<T, TArg, TEnvKey, TEnvVal, TError>(
+    cmd: impl AsRef<OsStr>,
+    args: impl IntoIterator<Item = TArg>,
+    envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    mut options: Options,
+) -> Result<T, TError>
+where
+    T: From<Vec<u8>>,
+    TArg: AsRef<OsStr>,
+    TEnvKey: AsRef<OsStr>,
+    TEnvVal: AsRef<OsStr>,
+    TError: From<Error<NeverError>>,
+{
+    let process = spawn(cmd, args, envs, &mut options).map_err(Error::ProcessNotSpawned)?;
+    let result = wait_with_output(process, options.parse_from()).map_err(Error::WaitFailed)?;
+
+    if !result.status.success() {
+        return Err(Error::ProcessFailed(result).into());
+    }
+
+    Ok(result.stdout.into())
+}
+
+/// Executes command with args and environment variables, returns raw output
+/// * On invalid command: panic
+/// * On error exit code: panic
+/// * On parsing failure: N/A
+/// * Possible errors: N/A
+///
+/// Designed for
+/// ```rust
+/// use shellfn::shell;
+///
+/// #[shell]
+/// fn command() -> Vec<u8> {
+///     "printf '\\x00\\xff'"
+/// }
+///
+/// assert_eq!(vec![0, 255], command())
+/// ```
+pub fn execute_bytes_panic<T, TArg, TEnvKey, TEnvVal>(
+    cmd: impl AsRef<OsStr>,
+    args: impl IntoIterator<Item = TArg>,
+    envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    mut options: Options,
+) -> T
+where
+    T: From<Vec<u8>>,
+    TArg: AsRef<OsStr>,
+    TEnvKey: AsRef<OsStr>,
+    TEnvVal: AsRef<OsStr>,
+{
+    let process = spawn(cmd, args, envs, &mut options).expect(PANIC_MSG);
+    let result = wait_with_output(process, options.parse_from()).expect(PANIC_MSG);
+
+    if !result.status.success() {
+        panic!("{}", PANIC_MSG);
+    }
+
+    result.stdout.into()
+}
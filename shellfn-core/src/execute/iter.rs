@@ -1,9 +1,11 @@
 use crate::error::Error;
-use crate::utils::{spawn, PANIC_MSG};
+use crate::metrics::counted;
+use crate::options::Options;
+use crate::parser::Parser;
+use crate::utils::{spawn, take_output, Process, PANIC_MSG};
 use itertools::Either;
-use std::error::Error as StdError;
 use std::ffi::OsStr;
-use std::io::{BufRead, BufReader};
+use std::fmt::Debug;
 use std::str::FromStr;
 
 /// Executes command with args and environment variables, parses output line by line
@@ -28,28 +30,53 @@ pub fn execute_iter_result_result<T, TArg, TEnvKey, TEnvVal, TOuterError, TInner
     cmd: impl AsRef<OsStr>,
     args: impl IntoIterator<Item = TArg>,
     envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    options: Options,
 ) -> Result<impl Iterator<Item = Result<T, TInnerError>>, TOuterError>
 where
     T: FromStr,
     TArg: AsRef<OsStr>,
     TEnvKey: AsRef<OsStr>,
     TEnvVal: AsRef<OsStr>,
-    <T as FromStr>::Err: StdError,
     TOuterError: From<Error<<T as FromStr>::Err>>,
     TInnerError: From<Error<<T as FromStr>::Err>>,
 {
-    let mut process = spawn(cmd, args, envs).map_err(Error::ProcessNotSpawned)?;
-    let stdout = process.stdout.take().unwrap();
-
-    Ok(BufReader::new(stdout).lines().map(|lres| {
-        lres.map_err(Error::StdoutUnreadable)
-            .map_err(Into::into)
-            .and_then(|line| {
-                line.parse()
-                    .map_err(Error::ParsingError)
-                    .map_err(Into::into)
-            })
-    }))
+    execute_iter_result_result_with(cmd, args, envs, options, str::parse::<T>)
+}
+
+/// Same as `execute_iter_result_result`, but parses every line with the given parser instead of `FromStr`
+pub fn execute_iter_result_result_with<
+    T,
+    TParser,
+    TArg,
+    TEnvKey,
+    TEnvVal,
+    TOuterError,
+    TInnerError,
+>(
+    cmd: impl AsRef<OsStr>,
+    args: impl IntoIterator<Item = TArg>,
+    envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    mut options: Options,
+    parser: TParser,
+) -> Result<impl Iterator<Item = Result<T, TInnerError>>, TOuterError>
+where
+    TParser: Parser<T>,
+    TArg: AsRef<OsStr>,
+    TEnvKey: AsRef<OsStr>,
+    TEnvVal: AsRef<OsStr>,
+    TOuterError: From<Error<TParser::Error>>,
+    TInnerError: From<Error<TParser::Error>>,
+{
+    let parser = counted(parser, &options);
+    let parse_from = options.parse_from();
+    let mut process = spawn(cmd, args, envs, &mut options).map_err(Error::ProcessNotSpawned)?;
+    let output = take_output(&mut process, parse_from);
+
+    let items = parser
+        .parse_records(output, options)
+        .map(|item| item.map_err(Into::into));
+
+    Ok(ProcessItems::new(items, process, false))
 }
 
 /// Executes command with args and environment variables, parses output line by line
@@ -74,26 +101,43 @@ pub fn execute_iter_panic_panic<T, TArg, TEnvKey, TEnvVal>(
     cmd: impl AsRef<OsStr>,
     args: impl IntoIterator<Item = TArg>,
     envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    options: Options,
 ) -> impl Iterator<Item = T>
 where
     T: FromStr,
     TArg: AsRef<OsStr>,
     TEnvKey: AsRef<OsStr>,
     TEnvVal: AsRef<OsStr>,
-    <T as FromStr>::Err: StdError,
+    <T as FromStr>::Err: Debug,
 {
-    let mut process = spawn(cmd, args, envs).expect(PANIC_MSG);
-    let stdout = process.stdout.take().unwrap();
-
-    BufReader::new(stdout)
-        .lines()
-        .map(|lres| lres.expect(PANIC_MSG).parse().expect(PANIC_MSG))
-        .chain([()].iter().flat_map(move |_| {
-            if !process.wait().unwrap().success() {
-                panic!("{}", PANIC_MSG)
-            }
-            std::iter::empty()
-        }))
+    execute_iter_panic_panic_with(cmd, args, envs, options, str::parse::<T>)
+}
+
+/// Same as `execute_iter_panic_panic`, but parses every line with the given parser instead of `FromStr`
+pub fn execute_iter_panic_panic_with<T, TParser, TArg, TEnvKey, TEnvVal>(
+    cmd: impl AsRef<OsStr>,
+    args: impl IntoIterator<Item = TArg>,
+    envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    mut options: Options,
+    parser: TParser,
+) -> impl Iterator<Item = T>
+where
+    TParser: Parser<T>,
+    TParser::Error: Debug,
+    TArg: AsRef<OsStr>,
+    TEnvKey: AsRef<OsStr>,
+    TEnvVal: AsRef<OsStr>,
+{
+    let parser = counted(parser, &options);
+    let parse_from = options.parse_from();
+    let mut process = spawn(cmd, args, envs, &mut options).expect(PANIC_MSG);
+    let output = take_output(&mut process, parse_from);
+
+    let items = parser
+        .parse_records(output, options)
+        .map(|item| item.expect(PANIC_MSG));
+
+    ProcessItems::new(items, process, true)
 }
 
 /// Executes command with args and environment variables, parses output line by line
@@ -118,35 +162,43 @@ pub fn execute_iter_panic_result<T, TArg, TEnvKey, TEnvVal, TError>(
     cmd: impl AsRef<OsStr>,
     args: impl IntoIterator<Item = TArg>,
     envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    options: Options,
 ) -> impl Iterator<Item = Result<T, TError>>
 where
     T: FromStr,
     TArg: AsRef<OsStr>,
     TEnvKey: AsRef<OsStr>,
     TEnvVal: AsRef<OsStr>,
-    <T as FromStr>::Err: StdError,
     TError: From<Error<<T as FromStr>::Err>>,
 {
-    let mut process = spawn(cmd, args, envs).expect(PANIC_MSG);
-    let stdout = process.stdout.take().unwrap();
-
-    BufReader::new(stdout)
-        .lines()
-        .map(|lres| {
-            lres.map_err(Error::StdoutUnreadable)
-                .map_err(Into::into)
-                .and_then(|line| {
-                    line.parse()
-                        .map_err(Error::ParsingError)
-                        .map_err(Into::into)
-                })
-        })
-        .chain([()].iter().flat_map(move |_| {
-            if !process.wait().unwrap().success() {
-                panic!("{}", PANIC_MSG)
-            }
-            std::iter::empty()
-        }))
+    execute_iter_panic_result_with(cmd, args, envs, options, str::parse::<T>)
+}
+
+/// Same as `execute_iter_panic_result`, but parses every line with the given parser instead of `FromStr`
+pub fn execute_iter_panic_result_with<T, TParser, TArg, TEnvKey, TEnvVal, TError>(
+    cmd: impl AsRef<OsStr>,
+    args: impl IntoIterator<Item = TArg>,
+    envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    mut options: Options,
+    parser: TParser,
+) -> impl Iterator<Item = Result<T, TError>>
+where
+    TParser: Parser<T>,
+    TArg: AsRef<OsStr>,
+    TEnvKey: AsRef<OsStr>,
+    TEnvVal: AsRef<OsStr>,
+    TError: From<Error<TParser::Error>>,
+{
+    let parser = counted(parser, &options);
+    let parse_from = options.parse_from();
+    let mut process = spawn(cmd, args, envs, &mut options).expect(PANIC_MSG);
+    let output = take_output(&mut process, parse_from);
+
+    let items = parser
+        .parse_records(output, options)
+        .map(|item| item.map_err(Into::into));
+
+    ProcessItems::new(items, process, true)
 }
 
 /// Executes command with args and environment variables, parses output line by line
@@ -171,29 +223,43 @@ pub fn execute_iter_nopanic_result<T, TArg, TEnvKey, TEnvVal, TError>(
     cmd: impl AsRef<OsStr>,
     args: impl IntoIterator<Item = TArg>,
     envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    options: Options,
 ) -> impl Iterator<Item = Result<T, TError>>
 where
     T: FromStr,
     TArg: AsRef<OsStr>,
     TEnvKey: AsRef<OsStr>,
     TEnvVal: AsRef<OsStr>,
-    <T as FromStr>::Err: StdError,
     TError: From<Error<<T as FromStr>::Err>>,
 {
-    spawn(cmd, args, envs)
+    execute_iter_nopanic_result_with(cmd, args, envs, options, str::parse::<T>)
+}
+
+/// Same as `execute_iter_nopanic_result`, but parses every line with the given parser instead of `FromStr`
+pub fn execute_iter_nopanic_result_with<T, TParser, TArg, TEnvKey, TEnvVal, TError>(
+    cmd: impl AsRef<OsStr>,
+    args: impl IntoIterator<Item = TArg>,
+    envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    mut options: Options,
+    parser: TParser,
+) -> impl Iterator<Item = Result<T, TError>>
+where
+    TParser: Parser<T>,
+    TArg: AsRef<OsStr>,
+    TEnvKey: AsRef<OsStr>,
+    TEnvVal: AsRef<OsStr>,
+    TError: From<Error<TParser::Error>>,
+{
+    let parser = counted(parser, &options);
+    let parse_from = options.parse_from();
+    spawn(cmd, args, envs, &mut options)
         .ok()
-        .map(|mut process| {
-            BufReader::new(process.stdout.take().unwrap())
-                .lines()
-                .map(|lres| {
-                    lres.map_err(Error::StdoutUnreadable)
-                        .map_err(Into::into)
-                        .and_then(|line| {
-                            line.parse()
-                                .map_err(Error::ParsingError)
-                                .map_err(Into::into)
-                        })
-                })
+        .map(move |mut process| {
+            let items = parser
+                .parse_records(take_output(&mut process, parse_from), options)
+                .map(|item| item.map_err(Into::into));
+
+            ProcessItems::new(items, process, false)
         })
         .map_or_else(|| Either::Right(std::iter::empty()), Either::Left)
 }
@@ -220,20 +286,41 @@ pub fn execute_iter_nopanic_nopanic<T, TArg, TEnvKey, TEnvVal>(
     cmd: impl AsRef<OsStr>,
     args: impl IntoIterator<Item = TArg>,
     envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    options: Options,
 ) -> impl Iterator<Item = T>
 where
     T: FromStr,
     TArg: AsRef<OsStr>,
     TEnvKey: AsRef<OsStr>,
     TEnvVal: AsRef<OsStr>,
-    <T as FromStr>::Err: StdError,
 {
-    spawn(cmd, args, envs)
+    execute_iter_nopanic_nopanic_with(cmd, args, envs, options, str::parse::<T>)
+}
+
+/// Same as `execute_iter_nopanic_nopanic`, but parses every line with the given parser instead of `FromStr`
+pub fn execute_iter_nopanic_nopanic_with<T, TParser, TArg, TEnvKey, TEnvVal>(
+    cmd: impl AsRef<OsStr>,
+    args: impl IntoIterator<Item = TArg>,
+    envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    mut options: Options,
+    parser: TParser,
+) -> impl Iterator<Item = T>
+where
+    TParser: Parser<T>,
+    TArg: AsRef<OsStr>,
+    TEnvKey: AsRef<OsStr>,
+    TEnvVal: AsRef<OsStr>,
+{
+    let parser = counted(parser, &options);
+    let parse_from = options.parse_from();
+    spawn(cmd, args, envs, &mut options)
         .ok()
-        .map(|mut process| {
-            BufReader::new(process.stdout.take().unwrap())
-                .lines()
-                .filter_map(|lres| lres.ok().and_then(|line| line.parse().ok()))
+        .map(move |mut process| {
+            let items = parser
+                .parse_records(take_output(&mut process, parse_from), options)
+                .filter_map(Result::ok);
+
+            ProcessItems::new(items, process, false)
         })
         .map_or_else(|| Either::Right(std::iter::empty()), Either::Left)
 }
@@ -260,21 +347,45 @@ pub fn execute_iter_result_panic<T, TArg, TEnvKey, TEnvVal, TError>(
     cmd: impl AsRef<OsStr>,
     args: impl IntoIterator<Item = TArg>,
     envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    options: Options,
 ) -> Result<impl Iterator<Item = T>, TError>
 where
     T: FromStr,
     TArg: AsRef<OsStr>,
     TEnvKey: AsRef<OsStr>,
     TEnvVal: AsRef<OsStr>,
-    <T as FromStr>::Err: StdError,
+    <T as FromStr>::Err: Debug,
     TError: From<Error<<T as FromStr>::Err>>,
 {
-    let mut process = spawn(cmd, args, envs).map_err(Error::ProcessNotSpawned)?;
-    let stdout = process.stdout.take().unwrap();
+    execute_iter_result_panic_with(cmd, args, envs, options, str::parse::<T>)
+}
+
+/// Same as `execute_iter_result_panic`, but parses every line with the given parser instead of `FromStr`
+pub fn execute_iter_result_panic_with<T, TParser, TArg, TEnvKey, TEnvVal, TError>(
+    cmd: impl AsRef<OsStr>,
+    args: impl IntoIterator<Item = TArg>,
+    envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    mut options: Options,
+    parser: TParser,
+) -> Result<impl Iterator<Item = T>, TError>
+where
+    TParser: Parser<T>,
+    TParser::Error: Debug,
+    TArg: AsRef<OsStr>,
+    TEnvKey: AsRef<OsStr>,
+    TEnvVal: AsRef<OsStr>,
+    TError: From<Error<TParser::Error>>,
+{
+    let parser = counted(parser, &options);
+    let parse_from = options.parse_from();
+    let mut process = spawn(cmd, args, envs, &mut options).map_err(Error::ProcessNotSpawned)?;
+    let output = take_output(&mut process, parse_from);
+
+    let items = parser
+        .parse_records(output, options)
+        .map(|item| item.expect(PANIC_MSG));
 
-    Ok(BufReader::new(stdout)
-        .lines()
-        .map(|lres| lres.expect(PANIC_MSG).parse().expect(PANIC_MSG)))
+    Ok(ProcessItems::new(items, process, false))
 }
 
 /// Executes command with args and environment variables, parses output line by line
@@ -299,19 +410,75 @@ pub fn execute_iter_result_nopanic<T, TArg, TEnvKey, TEnvVal, TError>(
     cmd: impl AsRef<OsStr>,
     args: impl IntoIterator<Item = TArg>,
     envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    options: Options,
 ) -> Result<impl Iterator<Item = T>, TError>
 where
     T: FromStr,
     TArg: AsRef<OsStr>,
     TEnvKey: AsRef<OsStr>,
     TEnvVal: AsRef<OsStr>,
-    <T as FromStr>::Err: StdError,
     TError: From<Error<<T as FromStr>::Err>>,
 {
-    let mut process = spawn(cmd, args, envs).map_err(Error::ProcessNotSpawned)?;
-    let stdout = process.stdout.take().unwrap();
+    execute_iter_result_nopanic_with(cmd, args, envs, options, str::parse::<T>)
+}
+
+/// Same as `execute_iter_result_nopanic`, but parses every line with the given parser instead of `FromStr`
+pub fn execute_iter_result_nopanic_with<T, TParser, TArg, TEnvKey, TEnvVal, TError>(
+    cmd: impl AsRef<OsStr>,
+    args: impl IntoIterator<Item = TArg>,
+    envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    mut options: Options,
+    parser: TParser,
+) -> Result<impl Iterator<Item = T>, TError>
+where
+    TParser: Parser<T>,
+    TArg: AsRef<OsStr>,
+    TEnvKey: AsRef<OsStr>,
+    TEnvVal: AsRef<OsStr>,
+    TError: From<Error<TParser::Error>>,
+{
+    let parser = counted(parser, &options);
+    let parse_from = options.parse_from();
+    let mut process = spawn(cmd, args, envs, &mut options).map_err(Error::ProcessNotSpawned)?;
+    let output = take_output(&mut process, parse_from);
+
+    let items = parser.parse_records(output, options).filter_map(Result::ok);
+
+    Ok(ProcessItems::new(items, process, false))
+}
+
+// yields the parsed items and waits for the process once they run out, the items are declared first, so when the
+// iterator is dropped early the reading end is closed before `Process` checks whether the child is done
+struct ProcessItems<I> {
+    items:            I,
+    process:          Process,
+    panic_on_failure: bool,
+}
+
+impl<I> ProcessItems<I> {
+    fn new(items: I, process: Process, panic_on_failure: bool) -> Self {
+        ProcessItems {
+            items,
+            process,
+            panic_on_failure,
+        }
+    }
+}
+
+impl<I: Iterator> Iterator for ProcessItems<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        let item = self.items.next();
+
+        if item.is_none() {
+            let success = self.process.wait().is_ok_and(|status| status.success());
+
+            if self.panic_on_failure && !success {
+                panic!("{}", PANIC_MSG)
+            }
+        }
 
-    Ok(BufReader::new(stdout)
-        .lines()
-        .filter_map(|lres| lres.ok().and_then(|item| item.parse().ok())))
+        item
+    }
 }
@@ -1,8 +1,10 @@
+mod event;
 mod item;
 mod iter;
 mod vec;
 mod void;
 
+pub use self::event::*;
 pub use self::item::*;
 pub use self::iter::*;
 pub use self::vec::*;
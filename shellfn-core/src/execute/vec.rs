@@ -1,9 +1,12 @@
 use crate::error::Error;
-use crate::execute::execute_iter_nopanic_nopanic;
+use crate::execute::execute_iter_nopanic_nopanic_with;
+use crate::metrics::counted;
+use crate::options::Options;
+use crate::parser::Parser;
 use crate::utils::*;
-use std::error::Error as StdError;
 use std::ffi::OsStr;
-use std::io::{BufRead, BufReader};
+use std::fmt::Debug;
+use std::iter::FromIterator;
 use std::str::FromStr;
 
 /// Executes command with args and environment variables, parses output line by line, returns after reading whole output
@@ -24,34 +27,58 @@ use std::str::FromStr;
 ///
 /// assert_eq!(vec![1, 2, 3], command().unwrap().map(Result::unwrap).collect::<Vec<_>>())
 /// ```
-pub fn execute_vec_result_result<T, TArg, TEnvKey, TEnvVal, TOuterError, TInnerError>(
+pub fn execute_vec_result_result<T, TCollection, TArg, TEnvKey, TEnvVal, TOuterError, TInnerError>(
     cmd: impl AsRef<OsStr>,
     args: impl IntoIterator<Item = TArg>,
     envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
-) -> Result<Vec<Result<T, TInnerError>>, TOuterError>
+    options: Options,
+) -> Result<TCollection, TOuterError>
 where
     T: FromStr,
+    TCollection: FromIterator<Result<T, TInnerError>>,
     TArg: AsRef<OsStr>,
     TEnvKey: AsRef<OsStr>,
     TEnvVal: AsRef<OsStr>,
-    <T as FromStr>::Err: StdError,
     TOuterError: From<Error<<T as FromStr>::Err>>,
     TInnerError: From<Error<<T as FromStr>::Err>>,
 {
-    let mut process = spawn(cmd, args, envs).map_err(Error::ProcessNotSpawned)?;
-    let stdout = process.stdout.take().unwrap();
-    let result = BufReader::new(stdout)
-        .lines()
-        .map(|lres| {
-            lres.map_err(Error::StdoutUnreadable)
-                .map_err(Into::into)
-                .and_then(|line| {
-                    line.parse()
-                        .map_err(Error::ParsingError)
-                        .map_err(Into::into)
-                })
-        })
-        .collect::<Vec<_>>();
+    execute_vec_result_result_with(cmd, args, envs, options, str::parse::<T>)
+}
+
+/// Same as `execute_vec_result_result`, but parses every line with the given parser instead of `FromStr`
+pub fn execute_vec_result_result_with<
+    T,
+    TCollection,
+    TParser,
+    TArg,
+    TEnvKey,
+    TEnvVal,
+    TOuterError,
+    TInnerError,
+>(
+    cmd: impl AsRef<OsStr>,
+    args: impl IntoIterator<Item = TArg>,
+    envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    mut options: Options,
+    parser: TParser,
+) -> Result<TCollection, TOuterError>
+where
+    TParser: Parser<T>,
+    TCollection: FromIterator<Result<T, TInnerError>>,
+    TArg: AsRef<OsStr>,
+    TEnvKey: AsRef<OsStr>,
+    TEnvVal: AsRef<OsStr>,
+    TOuterError: From<Error<TParser::Error>>,
+    TInnerError: From<Error<TParser::Error>>,
+{
+    let parser = counted(parser, &options);
+    let parse_from = options.parse_from();
+    let mut process = spawn(cmd, args, envs, &mut options).map_err(Error::ProcessNotSpawned)?;
+    let output = take_output(&mut process, parse_from);
+    let result = parser
+        .parse_records(output, options)
+        .map(|item| item.map_err(Into::into))
+        .collect();
 
     check_exit_code(process)?;
     Ok(result)
@@ -75,24 +102,47 @@ where
 ///
 /// assert_eq!(vec![1, 2, 3], command().collect::<Vec<_>>())
 /// ```
-pub fn execute_vec_panic_panic<T, TArg, TEnvKey, TEnvVal>(
+pub fn execute_vec_panic_panic<T, TCollection, TArg, TEnvKey, TEnvVal>(
     cmd: impl AsRef<OsStr>,
     args: impl IntoIterator<Item = TArg>,
     envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
-) -> Vec<T>
+    options: Options,
+) -> TCollection
 where
     T: FromStr,
+    TCollection: FromIterator<T>,
+    TArg: AsRef<OsStr>,
+    TEnvKey: AsRef<OsStr>,
+    TEnvVal: AsRef<OsStr>,
+    <T as FromStr>::Err: Debug,
+{
+    execute_vec_panic_panic_with(cmd, args, envs, options, str::parse::<T>)
+}
+
+/// Same as `execute_vec_panic_panic`, but parses every line with the given parser instead of `FromStr`
+pub fn execute_vec_panic_panic_with<T, TCollection, TParser, TArg, TEnvKey, TEnvVal>(
+    cmd: impl AsRef<OsStr>,
+    args: impl IntoIterator<Item = TArg>,
+    envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    mut options: Options,
+    parser: TParser,
+) -> TCollection
+where
+    TParser: Parser<T>,
+    TCollection: FromIterator<T>,
+    TParser::Error: Debug,
     TArg: AsRef<OsStr>,
     TEnvKey: AsRef<OsStr>,
     TEnvVal: AsRef<OsStr>,
-    <T as FromStr>::Err: StdError,
 {
-    let mut process = spawn(cmd, args, envs).expect(PANIC_MSG);
-    let stdout = process.stdout.take().unwrap();
-    let result = BufReader::new(stdout)
-        .lines()
-        .map(|lres| lres.expect(PANIC_MSG).parse().expect(PANIC_MSG))
-        .collect::<Vec<_>>();
+    let parser = counted(parser, &options);
+    let parse_from = options.parse_from();
+    let mut process = spawn(cmd, args, envs, &mut options).expect(PANIC_MSG);
+    let output = take_output(&mut process, parse_from);
+    let result = parser
+        .parse_records(output, options)
+        .map(|item| item.expect(PANIC_MSG))
+        .collect();
 
     check_exit_code_panic(process);
     result
@@ -116,33 +166,47 @@ where
 ///
 /// assert_eq!(vec![1, 2, 3], command().map(Result::unwrap).collect::<Vec<_>>())
 /// ```
-pub fn execute_vec_panic_result<T, TArg, TEnvKey, TEnvVal, TError>(
+pub fn execute_vec_panic_result<T, TCollection, TArg, TEnvKey, TEnvVal, TError>(
     cmd: impl AsRef<OsStr>,
     args: impl IntoIterator<Item = TArg>,
     envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
-) -> Vec<Result<T, TError>>
+    options: Options,
+) -> TCollection
 where
     T: FromStr,
+    TCollection: FromIterator<Result<T, TError>>,
     TArg: AsRef<OsStr>,
     TEnvKey: AsRef<OsStr>,
     TEnvVal: AsRef<OsStr>,
-    <T as FromStr>::Err: StdError,
     TError: From<Error<<T as FromStr>::Err>>,
 {
-    let mut process = spawn(cmd, args, envs).expect(PANIC_MSG);
-    let stdout = process.stdout.take().unwrap();
-    let result = BufReader::new(stdout)
-        .lines()
-        .map(|lres| {
-            lres.map_err(Error::StdoutUnreadable)
-                .map_err(Into::into)
-                .and_then(|line| {
-                    line.parse()
-                        .map_err(Error::ParsingError)
-                        .map_err(Into::into)
-                })
-        })
-        .collect::<Vec<_>>();
+    execute_vec_panic_result_with(cmd, args, envs, options, str::parse::<T>)
+}
+
+/// Same as `execute_vec_panic_result`, but parses every line with the given parser instead of `FromStr`
+pub fn execute_vec_panic_result_with<T, TCollection, TParser, TArg, TEnvKey, TEnvVal, TError>(
+    cmd: impl AsRef<OsStr>,
+    args: impl IntoIterator<Item = TArg>,
+    envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    mut options: Options,
+    parser: TParser,
+) -> TCollection
+where
+    TParser: Parser<T>,
+    TCollection: FromIterator<Result<T, TError>>,
+    TArg: AsRef<OsStr>,
+    TEnvKey: AsRef<OsStr>,
+    TEnvVal: AsRef<OsStr>,
+    TError: From<Error<TParser::Error>>,
+{
+    let parser = counted(parser, &options);
+    let parse_from = options.parse_from();
+    let mut process = spawn(cmd, args, envs, &mut options).expect(PANIC_MSG);
+    let output = take_output(&mut process, parse_from);
+    let result = parser
+        .parse_records(output, options)
+        .map(|item| item.map_err(Into::into))
+        .collect();
 
     check_exit_code_panic(process);
     result
@@ -166,35 +230,49 @@ where
 ///
 /// assert_eq!(vec![1, 2, 3], command().map(Result::unwrap).collect::<Vec<_>>())
 /// ```
-pub fn execute_vec_nopanic_result<T, TArg, TEnvKey, TEnvVal, TError>(
+pub fn execute_vec_nopanic_result<T, TCollection, TArg, TEnvKey, TEnvVal, TError>(
     cmd: impl AsRef<OsStr>,
     args: impl IntoIterator<Item = TArg>,
     envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
-) -> Vec<Result<T, TError>>
+    options: Options,
+) -> TCollection
 where
     T: FromStr,
+    TCollection: FromIterator<Result<T, TError>>,
     TArg: AsRef<OsStr>,
     TEnvKey: AsRef<OsStr>,
     TEnvVal: AsRef<OsStr>,
-    <T as FromStr>::Err: StdError,
     TError: From<Error<<T as FromStr>::Err>>,
 {
-    spawn(cmd, args, envs)
+    execute_vec_nopanic_result_with(cmd, args, envs, options, str::parse::<T>)
+}
+
+/// Same as `execute_vec_nopanic_result`, but parses every line with the given parser instead of `FromStr`
+pub fn execute_vec_nopanic_result_with<T, TCollection, TParser, TArg, TEnvKey, TEnvVal, TError>(
+    cmd: impl AsRef<OsStr>,
+    args: impl IntoIterator<Item = TArg>,
+    envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    mut options: Options,
+    parser: TParser,
+) -> TCollection
+where
+    TParser: Parser<T>,
+    TCollection: FromIterator<Result<T, TError>>,
+    TArg: AsRef<OsStr>,
+    TEnvKey: AsRef<OsStr>,
+    TEnvVal: AsRef<OsStr>,
+    TError: From<Error<TParser::Error>>,
+{
+    let parser = counted(parser, &options);
+    let parse_from = options.parse_from();
+    spawn(cmd, args, envs, &mut options)
         .map(|mut process| {
-            BufReader::new(process.stdout.take().unwrap())
-                .lines()
-                .map(|lres| {
-                    lres.map_err(Error::StdoutUnreadable)
-                        .map_err(Into::into)
-                        .and_then(|line| {
-                            line.parse()
-                                .map_err(Error::ParsingError)
-                                .map_err(Into::into)
-                        })
-                })
-                .collect::<Vec<_>>()
+            parser
+                .parse_records(take_output(&mut process, parse_from), options)
+                .map(|item| item.map_err(Into::into))
+                .collect()
         })
-        .unwrap_or(Vec::default())
+        .unwrap_or_else(|_| std::iter::empty().collect())
 }
 
 /// Executes command with args and environment variables, parses output line by line, returns after reading whole output
@@ -215,19 +293,39 @@ where
 ///
 /// assert_eq!(vec![1, 2, 3], command().collect::<Vec<_>>())
 /// ```
-pub fn execute_vec_nopanic_nopanic<T, TArg, TEnvKey, TEnvVal>(
+pub fn execute_vec_nopanic_nopanic<T, TCollection, TArg, TEnvKey, TEnvVal>(
     cmd: impl AsRef<OsStr>,
     args: impl IntoIterator<Item = TArg>,
     envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
-) -> Vec<T>
+    options: Options,
+) -> TCollection
 where
     T: FromStr,
+    TCollection: FromIterator<T>,
+    TArg: AsRef<OsStr>,
+    TEnvKey: AsRef<OsStr>,
+    TEnvVal: AsRef<OsStr>,
+{
+    execute_vec_nopanic_nopanic_with(cmd, args, envs, options, str::parse::<T>)
+}
+
+/// Same as `execute_vec_nopanic_nopanic`, but parses every line with the given parser instead of `FromStr`
+pub fn execute_vec_nopanic_nopanic_with<T, TCollection, TParser, TArg, TEnvKey, TEnvVal>(
+    cmd: impl AsRef<OsStr>,
+    args: impl IntoIterator<Item = TArg>,
+    envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    options: Options,
+    parser: TParser,
+) -> TCollection
+where
+    TParser: Parser<T>,
+    TCollection: FromIterator<T>,
     TArg: AsRef<OsStr>,
     TEnvKey: AsRef<OsStr>,
     TEnvVal: AsRef<OsStr>,
-    <T as FromStr>::Err: StdError,
 {
-    execute_iter_nopanic_nopanic(cmd, args, envs).collect()
+    let parser = counted(parser, &options);
+    execute_iter_nopanic_nopanic_with(cmd, args, envs, options, parser).collect()
 }
 
 /// Executes command with args and environment variables, parses output line by line, returns after reading whole output
@@ -248,26 +346,49 @@ where
 ///
 /// assert_eq!(vec![1, 2, 3], command().unwrap().collect::<Vec<_>>())
 /// ```
-pub fn execute_vec_result_panic<T, TArg, TEnvKey, TEnvVal, TError>(
+pub fn execute_vec_result_panic<T, TCollection, TArg, TEnvKey, TEnvVal, TError>(
     cmd: impl AsRef<OsStr>,
     args: impl IntoIterator<Item = TArg>,
     envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
-) -> Result<Vec<T>, TError>
+    options: Options,
+) -> Result<TCollection, TError>
 where
     T: FromStr,
+    TCollection: FromIterator<T>,
     TArg: AsRef<OsStr>,
     TEnvKey: AsRef<OsStr>,
     TEnvVal: AsRef<OsStr>,
-    <T as FromStr>::Err: StdError,
+    <T as FromStr>::Err: Debug,
     TError: From<Error<<T as FromStr>::Err>>,
 {
-    let mut process = spawn(cmd, args, envs).map_err(Error::ProcessNotSpawned)?;
-    let stdout = process.stdout.take().unwrap();
-    let mut result = Vec::new();
+    execute_vec_result_panic_with(cmd, args, envs, options, str::parse::<T>)
+}
 
-    for lres in BufReader::new(stdout).lines() {
-        result.push(lres.expect(PANIC_MSG).parse().expect(PANIC_MSG));
-    }
+/// Same as `execute_vec_result_panic`, but parses every line with the given parser instead of `FromStr`
+pub fn execute_vec_result_panic_with<T, TCollection, TParser, TArg, TEnvKey, TEnvVal, TError>(
+    cmd: impl AsRef<OsStr>,
+    args: impl IntoIterator<Item = TArg>,
+    envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    mut options: Options,
+    parser: TParser,
+) -> Result<TCollection, TError>
+where
+    TParser: Parser<T>,
+    TCollection: FromIterator<T>,
+    TParser::Error: Debug,
+    TArg: AsRef<OsStr>,
+    TEnvKey: AsRef<OsStr>,
+    TEnvVal: AsRef<OsStr>,
+    TError: From<Error<TParser::Error>>,
+{
+    let parser = counted(parser, &options);
+    let parse_from = options.parse_from();
+    let mut process = spawn(cmd, args, envs, &mut options).map_err(Error::ProcessNotSpawned)?;
+    let output = take_output(&mut process, parse_from);
+    let result = parser
+        .parse_records(output, options)
+        .map(|item| item.expect(PANIC_MSG))
+        .collect();
 
     check_exit_code(process)?;
     Ok(result)
@@ -291,25 +412,47 @@ where
 ///
 /// assert_eq!(vec![1, 2, 3], command().unwrap().collect::<Vec<_>>())
 /// ```
-pub fn execute_vec_result_nopanic<T, TArg, TEnvKey, TEnvVal, TError>(
+pub fn execute_vec_result_nopanic<T, TCollection, TArg, TEnvKey, TEnvVal, TError>(
     cmd: impl AsRef<OsStr>,
     args: impl IntoIterator<Item = TArg>,
     envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
-) -> Result<Vec<T>, TError>
+    options: Options,
+) -> Result<TCollection, TError>
 where
     T: FromStr,
+    TCollection: FromIterator<T>,
     TArg: AsRef<OsStr>,
     TEnvKey: AsRef<OsStr>,
     TEnvVal: AsRef<OsStr>,
-    <T as FromStr>::Err: StdError,
     TError: From<Error<<T as FromStr>::Err>>,
 {
-    let mut process = spawn(cmd, args, envs).map_err(Error::ProcessNotSpawned)?;
-    let stdout = process.stdout.take().unwrap();
-    let result = BufReader::new(stdout)
-        .lines()
-        .filter_map(|lres| lres.ok().and_then(|line| line.parse().ok()))
-        .collect::<Vec<_>>();
+    execute_vec_result_nopanic_with(cmd, args, envs, options, str::parse::<T>)
+}
+
+/// Same as `execute_vec_result_nopanic`, but parses every line with the given parser instead of `FromStr`
+pub fn execute_vec_result_nopanic_with<T, TCollection, TParser, TArg, TEnvKey, TEnvVal, TError>(
+    cmd: impl AsRef<OsStr>,
+    args: impl IntoIterator<Item = TArg>,
+    envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    mut options: Options,
+    parser: TParser,
+) -> Result<TCollection, TError>
+where
+    TParser: Parser<T>,
+    TCollection: FromIterator<T>,
+    TArg: AsRef<OsStr>,
+    TEnvKey: AsRef<OsStr>,
+    TEnvVal: AsRef<OsStr>,
+    TError: From<Error<TParser::Error>>,
+{
+    let parser = counted(parser, &options);
+    let parse_from = options.parse_from();
+    let mut process = spawn(cmd, args, envs, &mut options).map_err(Error::ProcessNotSpawned)?;
+    let output = take_output(&mut process, parse_from);
+    let result = parser
+        .parse_records(output, options)
+        .filter_map(Result::ok)
+        .collect();
 
     check_exit_code(process)?;
     Ok(result)
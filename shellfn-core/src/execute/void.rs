@@ -1,7 +1,8 @@
 use crate::error::{Error, NeverError};
-use crate::utils::{spawn, PANIC_MSG};
+use crate::options::Options;
+use crate::utils::{spawn, wait_with_output, PANIC_MSG};
 use std::ffi::OsStr;
-use std::process::{Child, Output};
+use std::process::Output;
 
 /// Executes command with args and environment variables, ignores output
 /// * On invalid command: do nothing
@@ -24,12 +25,14 @@ pub fn execute_void_nopanic<TArg, TEnvKey, TEnvVal>(
     cmd: impl AsRef<OsStr>,
     args: impl IntoIterator<Item = TArg>,
     envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    mut options: Options,
 ) where
     TArg: AsRef<OsStr>,
     TEnvKey: AsRef<OsStr>,
     TEnvVal: AsRef<OsStr>,
 {
-    let _ = spawn(cmd, args, envs).and_then(Child::wait_with_output);
+    let _ = spawn(cmd, args, envs, &mut options)
+        .and_then(|process| wait_with_output(process, options.parse_from()));
 }
 
 /// Executes command with args and environment variables, ignores output
@@ -53,13 +56,14 @@ pub fn execute_void_panic<TArg, TEnvKey, TEnvVal>(
     cmd: impl AsRef<OsStr>,
     args: impl IntoIterator<Item = TArg>,
     envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    mut options: Options,
 ) where
     TArg: AsRef<OsStr>,
     TEnvKey: AsRef<OsStr>,
     TEnvVal: AsRef<OsStr>,
 {
-    let output = spawn(cmd, args, envs)
-        .and_then(Child::wait_with_output)
+    let output = spawn(cmd, args, envs, &mut options)
+        .and_then(|process| wait_with_output(process, options.parse_from()))
         .expect(PANIC_MSG);
 
     if !output.status.success() {
@@ -88,6 +92,7 @@ pub fn execute_void_result<TArg, TEnvKey, TEnvVal, TError>(
     cmd: impl AsRef<OsStr>,
     args: impl IntoIterator<Item = TArg>,
     envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    mut options: Options,
 ) -> Result<(), TError>
 where
     TArg: AsRef<OsStr>,
@@ -95,7 +100,7 @@ where
     TEnvVal: AsRef<OsStr>,
     TError: From<Error<NeverError>>,
 {
-    let mut process = spawn(cmd, args, envs).map_err(Error::ProcessNotSpawned)?;
+    let mut process = spawn(cmd, args, envs, &mut options).map_err(Error::ProcessNotSpawned)?;
     let status = process.wait().map_err(Error::WaitFailed)?;
 
     if !status.success() {
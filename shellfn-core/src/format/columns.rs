@@ -0,0 +1,88 @@
+use crate::error::{ColumnError, Error};
+use crate::parser::Parser;
+use std::error::Error as StdError;
+use std::str::FromStr;
+
+/// Splits every record into columns and parses each of them with its own `FromStr`
+///
+/// Columns are separated with whitespace unless a delimiter is given.
+/// Used by the `#[shell]` attribute for tuple items, e.g. `Vec<(String, u32)>`
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Columns {
+    delimiter: Option<char>,
+}
+
+impl Columns {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = Some(delimiter);
+        self
+    }
+}
+
+impl<T: FromColumns> Parser<T> for Columns {
+    type Error = ColumnError;
+
+    fn parse(&mut self, input: &str) -> Result<T, Error<ColumnError>> {
+        let input = input.trim_end_matches(['\r', '\n']);
+
+        match self.delimiter {
+            Some(delimiter) => T::from_columns(&mut input.split(delimiter)),
+            None => T::from_columns(&mut input.split_whitespace()),
+        }
+        .map_err(Error::ParsingError)
+    }
+}
+
+/// Types that can be built from the columns of a single record, implemented for tuples of up to 12 elements
+pub trait FromColumns: Sized {
+    fn from_columns(columns: &mut dyn Iterator<Item = &str>) -> Result<Self, ColumnError>;
+}
+
+fn parse_column<T>(column: usize, value: Option<&str>) -> Result<T, ColumnError>
+where
+    T: FromStr,
+    T::Err: Into<Box<dyn StdError + Send + Sync>>,
+{
+    value
+        .ok_or(ColumnError::Missing { column })?
+        .parse()
+        .map_err(|e: T::Err| ColumnError::Invalid {
+            column,
+            source: e.into(),
+        })
+}
+
+macro_rules! impl_from_columns {
+    ($len:expr; $($index:tt $name:ident),+) => {
+        impl<$($name),+> FromColumns for ($($name,)+)
+        where
+            $($name: FromStr, $name::Err: Into<Box<dyn StdError + Send + Sync>>,)+
+        {
+            fn from_columns(columns: &mut dyn Iterator<Item = &str>) -> Result<Self, ColumnError> {
+                let value = ($(parse_column::<$name>($index, columns.next())?,)+);
+
+                match columns.next() {
+                    Some(_) => Err(ColumnError::Unexpected { column: $len }),
+                    None => Ok(value),
+                }
+            }
+        }
+    };
+}
+
+impl_from_columns!(1; 0 A);
+impl_from_columns!(2; 0 A, 1 B);
+impl_from_columns!(3; 0 A, 1 B, 2 C);
+impl_from_columns!(4; 0 A, 1 B, 2 C, 3 D);
+impl_from_columns!(5; 0 A, 1 B, 2 C, 3 D, 4 E);
+impl_from_columns!(6; 0 A, 1 B, 2 C, 3 D, 4 E, 5 F);
+impl_from_columns!(7; 0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G);
+impl_from_columns!(8; 0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G, 7 H);
+impl_from_columns!(9; 0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G, 7 H, 8 I);
+impl_from_columns!(10; 0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G, 7 H, 8 I, 9 J);
+impl_from_columns!(11; 0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G, 7 H, 8 I, 9 J, 10 K);
+impl_from_columns!(12; 0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G, 7 H, 8 I, 9 J, 10 K, 11 L);
@@ -0,0 +1,133 @@
+use crate::error::Error;
+use crate::options::{Options, Utf8};
+use crate::parser::Parser;
+use csv::{ByteRecord, Reader, ReaderBuilder, StringRecord};
+use serde::de::DeserializeOwned;
+use std::io::Read;
+
+/// Parses delimited tables (CSV, TSV) into `T: Deserialize`, one item per row
+///
+/// When the table has headers, columns are mapped into fields by name, otherwise by position.
+/// Quoted values may contain delimiters and newlines, so the records are not split with `Options::separator`.
+/// Used by the `#[shell(format = "csv")]` and `#[shell(format = "tsv")]` attributes
+#[derive(Debug, Copy, Clone)]
+pub struct Csv {
+    delimiter:   u8,
+    has_headers: bool,
+}
+
+impl Csv {
+    pub fn new() -> Self {
+        Self {
+            delimiter:   b',',
+            has_headers: true,
+        }
+    }
+
+    pub fn tsv() -> Self {
+        Self::new().with_delimiter(b'\t')
+    }
+
+    pub fn with_delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    pub fn with_headers(mut self, has_headers: bool) -> Self {
+        self.has_headers = has_headers;
+        self
+    }
+
+    fn reader<R: Read>(&self, reader: R, has_headers: bool) -> Reader<R> {
+        ReaderBuilder::new()
+            .delimiter(self.delimiter)
+            .has_headers(has_headers)
+            .from_reader(reader)
+    }
+}
+
+impl Default for Csv {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: DeserializeOwned> Parser<T> for Csv {
+    type Error = csv::Error;
+
+    // parses a single row without headers
+    fn parse(&mut self, input: &str) -> Result<T, Error<csv::Error>> {
+        let mut record = StringRecord::new();
+
+        self.reader(input.as_bytes(), false)
+            .read_record(&mut record)
+            .map_err(into_error)?;
+
+        record.deserialize(None).map_err(into_error)
+    }
+
+    fn parse_records<R: Read>(
+        self,
+        reader: R,
+        options: Options,
+    ) -> impl Iterator<Item = Result<T, Error<csv::Error>>> {
+        let mut reader = self.reader(reader, self.has_headers);
+        let mut headers = None;
+        let mut headers_pending = self.has_headers;
+
+        std::iter::from_fn(move || {
+            if headers_pending {
+                headers_pending = false;
+
+                match read_headers(&mut reader, &options) {
+                    Ok(h) => headers = Some(h),
+                    Err(e) => return Some(Err(into_error(e))),
+                }
+            }
+
+            match read_record(&mut reader, &options) {
+                Ok(Some(record)) => Some(record.deserialize(headers.as_ref()).map_err(into_error)),
+                Ok(None) => None,
+                Err(e) => Some(Err(into_error(e))),
+            }
+        })
+    }
+}
+
+fn read_headers<R: Read>(reader: &mut Reader<R>, options: &Options) -> csv::Result<StringRecord> {
+    match options.utf8() {
+        Utf8::Strict => reader.headers().cloned(),
+        Utf8::Lossy => Ok(StringRecord::from_byte_record_lossy(
+            reader.byte_headers()?.clone(),
+        )),
+    }
+}
+
+fn read_record<R: Read>(
+    reader: &mut Reader<R>,
+    options: &Options,
+) -> csv::Result<Option<StringRecord>> {
+    match options.utf8() {
+        Utf8::Strict => {
+            let mut record = StringRecord::new();
+            Ok(reader.read_record(&mut record)?.then_some(record))
+        }
+        Utf8::Lossy => {
+            let mut record = ByteRecord::new();
+            Ok(reader
+                .read_byte_record(&mut record)?
+                .then(|| StringRecord::from_byte_record_lossy(record)))
+        }
+    }
+}
+
+fn into_error(e: csv::Error) -> Error<csv::Error> {
+    if e.is_io_error() {
+        match e.into_kind() {
+            csv::ErrorKind::Io(e) => Error::StdoutUnreadable(e),
+            _ => unreachable!(),
+        }
+    } else {
+        Error::ParsingError(e)
+    }
+}
@@ -0,0 +1,71 @@
+use crate::error::{Error, NeverError};
+use crate::options::Options;
+use crate::parser::Parser;
+use crate::utils::records;
+use serde::de::DeserializeOwned;
+use std::io::Read;
+use std::iter;
+
+/// Parses the whole output as a single JSON value
+///
+/// Used by the `#[shell(format = "json")]` attribute
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Json;
+
+impl<T: DeserializeOwned> Parser<T> for Json {
+    type Error = NeverError;
+
+    fn parse(&mut self, input: &str) -> Result<T, Error<NeverError>> {
+        serde_json::from_str(input).map_err(|e| Error::JsonParsingError {
+            line:   e.line(),
+            column: e.column(),
+            source: Box::new(e),
+        })
+    }
+}
+
+/// Parses every line as a separate JSON value
+///
+/// Errors are reported with the number of the output line (counted from 1) instead of the line within the JSON value,
+/// also when lines are skipped or records span multiple lines. Used by the `#[shell(format = "jsonl")]` attribute
+#[derive(Debug, Default, Copy, Clone)]
+pub struct JsonLines {
+    line: usize,
+}
+
+impl<T: DeserializeOwned> Parser<T> for JsonLines {
+    type Error = NeverError;
+
+    // without the output, the line is the number of the parsed record
+    fn parse(&mut self, input: &str) -> Result<T, Error<NeverError>> {
+        self.line += 1;
+        parse_line(input, self.line)
+    }
+
+    fn parse_records<R: Read>(
+        self,
+        reader: R,
+        options: Options,
+    ) -> impl Iterator<Item = Result<T, Error<NeverError>>> {
+        let mut records = records(reader, options);
+
+        iter::from_fn(move || {
+            let record = records.next()?;
+
+            Some(
+                record
+                    .map_err(Error::StdoutUnreadable)
+                    .and_then(|record| parse_line(&record, records.line())),
+            )
+        })
+    }
+}
+
+// `line` is the line the record starts at, a record may span multiple lines with `Separator::Paragraph`
+fn parse_line<T: DeserializeOwned>(input: &str, line: usize) -> Result<T, Error<NeverError>> {
+    serde_json::from_str(input).map_err(|e| Error::JsonParsingError {
+        line:   line + e.line().saturating_sub(1),
+        column: e.column(),
+        source: Box::new(e),
+    })
+}
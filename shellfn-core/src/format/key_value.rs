@@ -0,0 +1,160 @@
+use crate::error::{Error, KeyValueError};
+use crate::parser::Parser;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error as StdError;
+use std::hash::{BuildHasher, Hash};
+use std::str::FromStr;
+
+/// Parses `key=value` lines into a map, both sides parsed with `FromStr`
+///
+/// Every line is split on the first occurrence of the separator and both sides are trimmed, so e.g. `key: value`
+/// lines can be parsed with `:` separator. Blank lines are skipped.
+/// Used by the `#[shell]` attribute for `HashMap`, `BTreeMap` and `IndexMap` return types
+#[derive(Debug, Clone)]
+pub struct KeyValue {
+    separator:  String,
+    duplicates: DuplicateKeys,
+}
+
+/// Handling of keys that occur more than once in the output
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum DuplicateKeys {
+    /// The last value is kept
+    #[default]
+    Last,
+    /// The first value is kept
+    First,
+    /// `KeyValueError::DuplicateKey` is reported
+    Error,
+}
+
+impl KeyValue {
+    pub fn new() -> Self {
+        Self {
+            separator:  "=".to_string(),
+            duplicates: DuplicateKeys::default(),
+        }
+    }
+
+    pub fn with_separator(mut self, separator: impl Into<String>) -> Self {
+        self.separator = separator.into();
+        self
+    }
+
+    pub fn with_duplicates(mut self, duplicates: DuplicateKeys) -> Self {
+        self.duplicates = duplicates;
+        self
+    }
+}
+
+impl Default for KeyValue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M> Parser<M> for KeyValue
+where
+    M: KeyValueMap,
+    M::Key: FromStr,
+    <M::Key as FromStr>::Err: Into<Box<dyn StdError + Send + Sync>>,
+    M::Value: FromStr,
+    <M::Value as FromStr>::Err: Into<Box<dyn StdError + Send + Sync>>,
+{
+    type Error = KeyValueError;
+
+    fn parse(&mut self, input: &str) -> Result<M, Error<KeyValueError>> {
+        let mut map = M::default();
+
+        for line in input.lines().filter(|line| !line.trim().is_empty()) {
+            let (key, value) = line
+                .split_once(self.separator.as_str())
+                .ok_or_else(|| KeyValueError::MissingSeparator {
+                    line: line.to_string(),
+                })
+                .map_err(Error::ParsingError)?;
+            let (key, value) = (key.trim(), value.trim());
+
+            let parsed_key = key
+                .parse::<M::Key>()
+                .map_err(|e| KeyValueError::InvalidKey {
+                    key:    key.to_string(),
+                    source: e.into(),
+                })
+                .map_err(Error::ParsingError)?;
+
+            if map.contains_key(&parsed_key) {
+                match self.duplicates {
+                    DuplicateKeys::Last => {}
+                    DuplicateKeys::First => continue,
+                    DuplicateKeys::Error => {
+                        return Err(Error::ParsingError(KeyValueError::DuplicateKey {
+                            key: key.to_string(),
+                        }))
+                    }
+                }
+            }
+
+            let parsed_value = value
+                .parse::<M::Value>()
+                .map_err(|e| KeyValueError::InvalidValue {
+                    key:    key.to_string(),
+                    source: e.into(),
+                })
+                .map_err(Error::ParsingError)?;
+
+            map.insert(parsed_key, parsed_value);
+        }
+
+        Ok(map)
+    }
+}
+
+/// Maps that can be returned from `key=value` lines
+pub trait KeyValueMap: Default {
+    type Key;
+    type Value;
+
+    fn contains_key(&self, key: &Self::Key) -> bool;
+    fn insert(&mut self, key: Self::Key, value: Self::Value);
+}
+
+impl<K: Eq + Hash, V, S: BuildHasher + Default> KeyValueMap for HashMap<K, V, S> {
+    type Key = K;
+    type Value = V;
+
+    fn contains_key(&self, key: &K) -> bool {
+        HashMap::contains_key(self, key)
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        HashMap::insert(self, key, value);
+    }
+}
+
+impl<K: Ord, V> KeyValueMap for BTreeMap<K, V> {
+    type Key = K;
+    type Value = V;
+
+    fn contains_key(&self, key: &K) -> bool {
+        BTreeMap::contains_key(self, key)
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        BTreeMap::insert(self, key, value);
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl<K: Eq + Hash, V, S: BuildHasher + Default> KeyValueMap for indexmap::IndexMap<K, V, S> {
+    type Key = K;
+    type Value = V;
+
+    fn contains_key(&self, key: &K) -> bool {
+        indexmap::IndexMap::contains_key(self, key)
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        indexmap::IndexMap::insert(self, key, value);
+    }
+}
@@ -0,0 +1,17 @@
+mod columns;
+#[cfg(feature = "csv")]
+mod delimited;
+#[cfg(feature = "serde")]
+mod json;
+mod key_value;
+#[cfg(feature = "csv")]
+mod table;
+
+pub use self::columns::*;
+#[cfg(feature = "csv")]
+pub use self::delimited::*;
+#[cfg(feature = "serde")]
+pub use self::json::*;
+pub use self::key_value::*;
+#[cfg(feature = "csv")]
+pub use self::table::*;
@@ -0,0 +1,144 @@
+use crate::error::{Error, TableError};
+use crate::options::Options;
+use crate::parser::Parser;
+use crate::utils::records;
+use csv::StringRecord;
+use serde::de::DeserializeOwned;
+use std::io::{self, Read};
+use std::ops::Range;
+
+/// Parses column-aligned tables with a header line, like the output of `df` or `ps`, into `T: Deserialize`
+///
+/// Column boundaries are inferred from the positions that are blank in every line, so the whole output is read
+/// before the first item is produced. Columns are mapped into fields by the header names.
+/// Used by the `#[shell(format = "table")]` attribute
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Table;
+
+impl<T: DeserializeOwned> Parser<T> for Table {
+    type Error = TableError;
+
+    // parses the header line and the first row
+    fn parse(&mut self, input: &str) -> Result<T, Error<TableError>> {
+        let lines = input.lines().map(str::to_string).collect::<Vec<_>>();
+
+        deserialize_rows(&lines)
+            .into_iter()
+            .next()
+            .unwrap_or(Err(Error::ParsingError(TableError::NoRows)))
+    }
+
+    fn parse_records<R: Read>(
+        self,
+        reader: R,
+        options: Options,
+    ) -> impl Iterator<Item = Result<T, Error<TableError>>> {
+        match records(reader, options).collect::<io::Result<Vec<_>>>() {
+            Ok(lines) => deserialize_rows(&lines),
+            Err(e) => vec![Err(Error::StdoutUnreadable(e))],
+        }
+        .into_iter()
+    }
+}
+
+fn deserialize_rows<T: DeserializeOwned>(lines: &[String]) -> Vec<Result<T, Error<TableError>>> {
+    let lines = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.chars().collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+
+    let header = match lines.first() {
+        Some(header) => header,
+        None => return Vec::new(),
+    };
+    let columns = columns(&lines);
+    let headers = record(header, &columns);
+
+    lines[1..]
+        .iter()
+        .enumerate()
+        .map(|(index, line)| {
+            record(line, &columns)
+                .deserialize(Some(&headers))
+                .map_err(|source| {
+                    Error::ParsingError(TableError::InvalidRow {
+                        row: index + 1,
+                        source,
+                    })
+                })
+        })
+        .collect()
+}
+
+// splits the lines on positions that are blank in every line, then merges the parts that cannot be separate columns:
+// - parts without a header, e.g. words of the last value in `ps aux` or `3 (2d ago)` in `kubectl get pods`
+// - parts after the start of the last header, which always spans to the end of the line
+// - parts without any value separated from the previous one with a single space, e.g. `Mounted on` in `df`
+fn columns(lines: &[Vec<char>]) -> Vec<Range<usize>> {
+    let header = &lines[0];
+    let width = lines.iter().map(Vec::len).max().unwrap_or(0);
+    let is_blank = |position: usize| lines.iter().all(|line| is_blank_at(line, position));
+    let last_header_start = header
+        .iter()
+        .rposition(|c| !c.is_whitespace())
+        .map(|end| {
+            header[..end]
+                .iter()
+                .rposition(|c| c.is_whitespace())
+                .map_or(0, |blank| blank + 1)
+        })
+        .unwrap_or(0);
+
+    let mut columns: Vec<Range<usize>> = Vec::new();
+    let mut start = None;
+
+    for position in 0..=width {
+        match (start, position < width && !is_blank(position)) {
+            (None, true) => start = Some(position),
+            (Some(part_start), false) => {
+                let part = part_start..position;
+                let has_values = lines[1..].iter().any(|line| !cell(line, &part).is_empty());
+
+                match columns.last_mut() {
+                    Some(previous)
+                        if cell(header, &part).is_empty()
+                            || part.start > last_header_start
+                            || (part.start - previous.end == 1 && !has_values) =>
+                    {
+                        previous.end = part.end
+                    }
+                    _ => columns.push(part),
+                }
+
+                start = None;
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(last) = columns.last_mut() {
+        last.end = usize::MAX;
+    }
+
+    columns
+}
+
+fn is_blank_at(line: &[char], position: usize) -> bool {
+    line.get(position).is_none_or(|c| c.is_whitespace())
+}
+
+fn cell(line: &[char], range: &Range<usize>) -> String {
+    let start = range.start.min(line.len());
+    let end = range.end.min(line.len());
+
+    line[start..end]
+        .iter()
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+fn record(line: &[char], columns: &[Range<usize>]) -> StringRecord {
+    columns.iter().map(|range| cell(line, range)).collect()
+}
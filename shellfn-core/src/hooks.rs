@@ -0,0 +1,220 @@
+use crate::secret::{Secrets, REDACTED};
+use std::borrow::Cow;
+use std::ffi::{OsStr, OsString};
+use std::fmt::{self, Debug};
+use std::io;
+use std::process::ExitStatus;
+use std::sync::{Arc, PoisonError, RwLock};
+
+type BeforeSpawn = Arc<dyn Fn(&mut Invocation) -> Result<(), Veto> + Send + Sync>;
+type AfterExit = Arc<dyn Fn(&Invocation, &Outcome) + Send + Sync>;
+
+static BEFORE_SPAWN: RwLock<Vec<BeforeSpawn>> = RwLock::new(Vec::new());
+static AFTER_EXIT: RwLock<Vec<AfterExit>> = RwLock::new(Vec::new());
+
+/// Registers a hook called before every subprocess is spawned, in the order of registration
+///
+/// The hook can modify the command, its args and environment variables, or veto the call. A vetoed call fails like
+/// a subprocess that could not be spawned, with `io::ErrorKind::PermissionDenied`
+pub fn before_spawn(hook: impl Fn(&mut Invocation) -> Result<(), Veto> + Send + Sync + 'static) {
+    BEFORE_SPAWN
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .push(Arc::new(hook));
+}
+
+/// Registers a hook called once the subprocess is waited for, or when it could not be spawned or was vetoed
+///
+/// The exit of a subprocess still running when its iterator is dropped is reported from a background thread
+pub fn after_exit(hook: impl Fn(&Invocation, &Outcome) + Send + Sync + 'static) {
+    AFTER_EXIT
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .push(Arc::new(hook));
+}
+
+/// Command about to be executed by the function named with `Options::with_name`
+///
+/// Values of the secret environment variables are redacted in its `Debug` output
+#[derive(Clone)]
+pub struct Invocation {
+    name:        Option<String>,
+    cmd:         OsString,
+    args:        Vec<OsString>,
+    envs:        Vec<(OsString, OsString)>,
+    secret_envs: Vec<OsString>,
+}
+
+/// Result of the invocation passed to the `after_exit` hooks
+#[derive(Debug)]
+pub enum Outcome<'a> {
+    Exited(ExitStatus),
+    SpawnFailed(&'a io::Error),
+}
+
+/// Reason of rejecting the invocation in a `before_spawn` hook
+#[derive(thiserror::Error, Debug, Clone, Eq, PartialEq)]
+#[error("invocation vetoed: {reason}")]
+pub struct Veto {
+    reason: String,
+}
+
+impl Veto {
+    pub fn new(reason: impl Into<String>) -> Self {
+        Self {
+            reason: reason.into(),
+        }
+    }
+
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+}
+
+impl Invocation {
+    pub fn new<TArg, TEnvKey, TEnvVal>(
+        name: Option<&str>,
+        cmd: impl AsRef<OsStr>,
+        args: impl IntoIterator<Item = TArg>,
+        envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
+    ) -> Self
+    where
+        TArg: AsRef<OsStr>,
+        TEnvKey: AsRef<OsStr>,
+        TEnvVal: AsRef<OsStr>,
+    {
+        Self {
+            name:        name.map(str::to_string),
+            cmd:         cmd.as_ref().to_os_string(),
+            args:        args
+                .into_iter()
+                .map(|arg| arg.as_ref().to_os_string())
+                .collect(),
+            envs:        envs
+                .into_iter()
+                .map(|(key, val)| (key.as_ref().to_os_string(), val.as_ref().to_os_string()))
+                .collect(),
+            secret_envs: Vec::new(),
+        }
+    }
+
+    /// Marks the environment variables as secret, see `Options::with_secret_envs`
+    pub fn with_secret_envs(mut self, keys: impl IntoIterator<Item = impl Into<OsString>>) -> Self {
+        self.secret_envs.extend(keys.into_iter().map(Into::into));
+        self
+    }
+
+    /// Path of the Rust function, see `Options::with_name`
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn cmd(&self) -> &OsStr {
+        &self.cmd
+    }
+
+    pub fn set_cmd(&mut self, cmd: impl Into<OsString>) {
+        self.cmd = cmd.into();
+    }
+
+    pub fn args(&self) -> &[OsString] {
+        &self.args
+    }
+
+    pub fn args_mut(&mut self) -> &mut Vec<OsString> {
+        &mut self.args
+    }
+
+    /// Environment variables set for the subprocess on top of the inherited ones
+    pub fn envs(&self) -> &[(OsString, OsString)] {
+        &self.envs
+    }
+
+    pub fn env(&self, key: impl AsRef<OsStr>) -> Option<&OsStr> {
+        self.envs
+            .iter()
+            .find(|(k, _)| k == key.as_ref())
+            .map(|(_, val)| val.as_os_str())
+    }
+
+    /// Sets the environment variable, replacing its previous value
+    pub fn set_env(&mut self, key: impl Into<OsString>, val: impl Into<OsString>) {
+        let key = key.into();
+        self.remove_env(&key);
+        self.envs.push((key, val.into()));
+    }
+
+    pub fn remove_env(&mut self, key: impl AsRef<OsStr>) {
+        self.envs.retain(|(k, _)| k != key.as_ref());
+    }
+
+    pub fn is_secret_env(&self, key: impl AsRef<OsStr>) -> bool {
+        self.secret_envs.iter().any(|k| k == key.as_ref())
+    }
+
+    /// Replaces the current values of the secret environment variables in the text with `***`
+    pub fn redact<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        self.secrets().redact_str(text)
+    }
+
+    pub fn secrets(&self) -> Secrets {
+        Secrets::new(
+            self.envs
+                .iter()
+                .filter(|(key, _)| self.is_secret_env(key))
+                .map(|(_, val)| val.as_encoded_bytes().to_vec()),
+        )
+    }
+}
+
+impl Debug for Invocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let secrets = self.secrets();
+        let args = self
+            .args
+            .iter()
+            .map(|arg| secrets.redact_str(&arg.to_string_lossy()).into_owned())
+            .collect::<Vec<_>>();
+        let envs = self
+            .envs
+            .iter()
+            .map(|(key, val)| {
+                let val = if self.is_secret_env(key) {
+                    REDACTED.to_string()
+                } else {
+                    val.to_string_lossy().into_owned()
+                };
+
+                (key.to_string_lossy().into_owned(), val)
+            })
+            .collect::<Vec<_>>();
+
+        f.debug_struct("Invocation")
+            .field("name", &self.name)
+            .field("cmd", &self.cmd)
+            .field("args", &args)
+            .field("envs", &envs)
+            .finish()
+    }
+}
+
+/// Runs the `before_spawn` hooks, a veto stops the remaining ones
+pub fn run_before_spawn(invocation: &mut Invocation) -> io::Result<()> {
+    for hook in registered(&BEFORE_SPAWN) {
+        hook(invocation).map_err(|veto| io::Error::new(io::ErrorKind::PermissionDenied, veto))?;
+    }
+
+    Ok(())
+}
+
+pub fn run_after_exit(invocation: &Invocation, outcome: &Outcome) {
+    for hook in registered(&AFTER_EXIT) {
+        hook(invocation, outcome);
+    }
+}
+
+// the hooks are called without holding the lock, so a hook can register another one or call a shell function, and a
+// panicking hook does not poison the registry
+fn registered<T: ?Sized>(hooks: &RwLock<Vec<Arc<T>>>) -> Vec<Arc<T>> {
+    hooks.read().unwrap_or_else(PoisonError::into_inner).clone()
+}
@@ -1,6 +1,85 @@
+#[cfg(feature = "audit")]
+mod audit;
 mod error;
+mod event;
 mod execute;
+mod format;
+mod hooks;
+mod line;
+#[cfg(feature = "log")]
+mod log_bridge;
+mod metrics;
+mod options;
+mod parser;
+mod pipe;
+mod progress;
+mod secret;
+mod trace;
 mod utils;
 
-pub use crate::error::Error;
+#[cfg(feature = "audit")]
+pub use crate::audit::{set_audit_log, AUDIT_LOG_ENV};
+#[cfg(feature = "csv")]
+pub use crate::error::TableError;
+pub use crate::error::{ColumnError, DebugError, Error, KeyValueError, LineError, NeverError};
+pub use crate::event::ShellEvent;
 pub use crate::execute::*;
+pub use crate::format::*;
+pub use crate::hooks::{after_exit, before_spawn, Invocation, Outcome, Veto};
+pub use crate::line::*;
+pub use crate::options::{Options, ParseFrom, Separator, Utf8};
+pub use crate::parser::Parser;
+pub use crate::progress::{Progress, ProgressCallback};
+
+#[cfg(feature = "tracing")]
+#[doc(hidden)]
+pub use tracing as __tracing;
+
+/// Opens the span of a `#[shell]` function call, used by the generated code
+///
+/// Defined here rather than generated, so the span depends on the `tracing` feature of this crate and not on the
+/// features of the crate using the attribute
+#[cfg(feature = "tracing")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __trace {
+    ($options:expr, $name:literal, $interpreter:expr, $envs:expr) => {
+        $options.with_span($crate::__tracing::info_span!(
+            $name,
+            interpreter = $interpreter,
+            envs = $envs,
+            pid = $crate::__tracing::field::Empty,
+            exit_code = $crate::__tracing::field::Empty,
+            duration_ms = $crate::__tracing::field::Empty,
+            lines = $crate::__tracing::field::Empty,
+        ))
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __trace {
+    ($options:expr, $name:literal, $interpreter:expr, $envs:expr) => {
+        $options
+    };
+}
+
+// the log target is set by a macro from shellfn-core, so `log_bridge` without its `log` feature fails with a message
+#[cfg(feature = "log")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __log_target {
+    ($options:expr, $target:expr) => {
+        $options.with_log_target($target)
+    };
+}
+
+#[cfg(not(feature = "log"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __log_target {
+    ($options:expr, $target:expr) => {
+        compile_error!("log_bridge requires the `log` feature of shellfn")
+    };
+}
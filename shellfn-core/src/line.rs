@@ -0,0 +1,21 @@
+use crate::error::LineError;
+use std::error::Error as StdError;
+use std::str::FromStr;
+
+#[cfg(feature = "regex")]
+pub use regex::Regex;
+
+/// Parses a single field of a struct deriving `FromShellLine`
+pub fn parse_field<T>(field: &'static str, value: Option<&str>) -> Result<T, LineError>
+where
+    T: FromStr,
+    T::Err: Into<Box<dyn StdError + Send + Sync>>,
+{
+    value
+        .ok_or(LineError::MissingField { field })?
+        .parse()
+        .map_err(|e: T::Err| LineError::InvalidField {
+            field,
+            source: e.into(),
+        })
+}
@@ -0,0 +1,32 @@
+use crate::secret::Secrets;
+use log::Level;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::str::FromStr;
+use std::thread::{self, JoinHandle};
+
+/// Environment variable with the number of the file descriptor `shellfn_log` writes to
+pub const LOG_FD_ENV: &str = "SHELLFN_LOG_FD";
+
+/// Reads `<level> <message>` lines written by `shellfn_log` and forwards them to the `log` facade with the given
+/// target until the subprocess closes the pipe. Secret values are redacted
+pub fn forward_records(reader: File, target: String, secrets: Secrets) -> JoinHandle<()> {
+    thread::spawn(move || {
+        for line in BufReader::new(reader).lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            let (level, message) = parse_record(&line);
+
+            log::log!(target: &target, level, "{}", secrets.redact_str(message));
+        }
+    })
+}
+
+// lines without a known level are logged as they are with `Level::Info`
+fn parse_record(line: &str) -> (Level, &str) {
+    line.split_once(' ')
+        .and_then(|(level, message)| Some((Level::from_str(level).ok()?, message)))
+        .unwrap_or((Level::Info, line))
+}
@@ -0,0 +1,160 @@
+use crate::error::Error;
+use crate::options::Options;
+use crate::parser::Parser;
+use std::io::Read;
+use std::process::ExitStatus;
+#[cfg(feature = "metrics")]
+use std::sync::Arc;
+#[cfg(feature = "metrics")]
+use std::time::Instant;
+
+#[cfg(feature = "metrics")]
+pub const INVOCATIONS: &str = "shellfn_invocations_total";
+#[cfg(feature = "metrics")]
+pub const SPAWN_FAILURES: &str = "shellfn_spawn_failures_total";
+#[cfg(feature = "metrics")]
+pub const FAILED_EXITS: &str = "shellfn_failed_exits_total";
+#[cfg(feature = "metrics")]
+pub const PARSE_FAILURES: &str = "shellfn_parse_failures_total";
+#[cfg(feature = "metrics")]
+pub const DURATION: &str = "shellfn_duration_seconds";
+#[cfg(feature = "metrics")]
+const FUNCTION_LABEL: &str = "function";
+
+/// Records the call of the function named with `Options::with_name` in the `metrics` facade, does nothing without
+/// the `metrics` feature or the name
+///
+/// The duration is recorded when the last clone is dropped
+#[derive(Clone, Default)]
+pub struct Metrics {
+    #[cfg(feature = "metrics")]
+    inner: Option<Arc<Inner>>,
+}
+
+#[cfg(feature = "metrics")]
+struct Inner {
+    function: String,
+    started:  Instant,
+}
+
+#[cfg(feature = "metrics")]
+impl Metrics {
+    /// Counts the invocation
+    pub fn new(options: &Options) -> Self {
+        let inner = options.name().map(|function| {
+            metrics::counter!(INVOCATIONS, FUNCTION_LABEL => function.to_string()).increment(1);
+
+            Arc::new(Inner {
+                function: function.to_string(),
+                started:  Instant::now(),
+            })
+        });
+
+        Self { inner }
+    }
+
+    pub fn spawn_failed(&self) {
+        if let Some(ref inner) = self.inner {
+            metrics::counter!(SPAWN_FAILURES, FUNCTION_LABEL => inner.function.clone())
+                .increment(1);
+        }
+    }
+
+    pub fn exited(&self, status: ExitStatus) {
+        if let Some(ref inner) = self.inner {
+            if !status.success() {
+                metrics::counter!(FAILED_EXITS, FUNCTION_LABEL => inner.function.clone())
+                    .increment(1);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl Drop for Inner {
+    fn drop(&mut self) {
+        metrics::histogram!(DURATION, FUNCTION_LABEL => self.function.clone())
+            .record(self.started.elapsed().as_secs_f64());
+    }
+}
+
+#[cfg(not(feature = "metrics"))]
+impl Metrics {
+    pub fn new(_options: &Options) -> Self {
+        Self {}
+    }
+
+    pub fn spawn_failed(&self) {}
+
+    pub fn exited(&self, _status: ExitStatus) {}
+}
+
+/// Parser counting its parsing errors in the `metrics` facade, see `counted`
+pub struct CountedParser<P> {
+    parser:  P,
+    counter: ParseFailures,
+}
+
+/// Wraps the parser, so its parsing errors are counted for the function named with `Options::with_name`
+pub fn counted<P>(parser: P, options: &Options) -> CountedParser<P> {
+    CountedParser {
+        parser,
+        counter: ParseFailures::new(options),
+    }
+}
+
+struct ParseFailures {
+    #[cfg(feature = "metrics")]
+    function: Option<String>,
+}
+
+#[cfg(feature = "metrics")]
+impl ParseFailures {
+    fn new(options: &Options) -> Self {
+        Self {
+            function: options.name().map(str::to_string),
+        }
+    }
+
+    fn count<T, E>(&self, result: &Result<T, Error<E>>) {
+        if let (Some(function), Err(e)) = (&self.function, result) {
+            if e.is_parsing_error() {
+                metrics::counter!(PARSE_FAILURES, FUNCTION_LABEL => function.clone()).increment(1);
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "metrics"))]
+impl ParseFailures {
+    fn new(_options: &Options) -> Self {
+        Self {}
+    }
+
+    fn count<T, E>(&self, _result: &Result<T, Error<E>>) {}
+}
+
+impl<T, P> Parser<T> for CountedParser<P>
+where
+    P: Parser<T>,
+{
+    type Error = P::Error;
+
+    fn parse(&mut self, input: &str) -> Result<T, Error<Self::Error>> {
+        let result = self.parser.parse(input);
+        self.counter.count(&result);
+        result
+    }
+
+    fn parse_records<R: Read>(
+        self,
+        reader: R,
+        options: Options,
+    ) -> impl Iterator<Item = Result<T, Error<Self::Error>>> {
+        let counter = self.counter;
+
+        self.parser
+            .parse_records(reader, options)
+            .inspect(move |result| counter.count(result))
+    }
+}
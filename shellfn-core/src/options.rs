@@ -0,0 +1,221 @@
+use crate::progress::{Progress, ProgressCallback};
+use crate::secret::Secrets;
+
+/// Runtime options of the execute functions, filled by the `#[shell]` attribute
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    utf8:          Utf8,
+    separator:     Separator,
+    none_codes:    Vec<i32>,
+    trim:          bool,
+    skip_empty:    bool,
+    strip_ansi:    bool,
+    skip_comments: Option<String>,
+    parse_from:    ParseFrom,
+    name:          Option<String>,
+    script_arg:    Option<usize>,
+    secret_envs:   Vec<String>,
+    // values of `secret_envs`, resolved when the subprocess is spawned
+    secrets:       Secrets,
+    #[cfg(feature = "log")]
+    log_target:    Option<String>,
+    progress:      Option<ProgressCallback>,
+    #[cfg(feature = "tracing")]
+    span:          Option<tracing::Span>,
+}
+
+/// Decoding of the subprocess output
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum Utf8 {
+    /// Invalid UTF-8 sequences are reported as errors
+    #[default]
+    Strict,
+    /// Invalid UTF-8 sequences are replaced with `U+FFFD REPLACEMENT CHARACTER`
+    Lossy,
+}
+
+/// Delimiter of the records in the subprocess output, used by iterator and vec execute functions
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum Separator {
+    /// Records are separated with `\n` or `\r\n`
+    #[default]
+    Newline,
+    /// Records are separated with the given byte, e.g. `\0` for `find -print0`
+    Byte(u8),
+    /// Records are blocks of lines separated with one or more blank lines
+    Paragraph,
+}
+
+/// Stream of the subprocess that is parsed
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum ParseFrom {
+    /// Standard output, standard error is inherited
+    #[default]
+    Stdout,
+    /// Standard error, standard output is inherited
+    Stderr,
+    /// Both streams, interleaved line by line in the order the lines are written
+    Both,
+    /// Extra pipe opened as the given file descriptor (3 or greater) in the subprocess, both standard streams are
+    /// inherited. Supported only on Unix
+    Fd(i32),
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_utf8(mut self, utf8: Utf8) -> Self {
+        self.utf8 = utf8;
+        self
+    }
+
+    pub fn with_separator(mut self, separator: Separator) -> Self {
+        self.separator = separator;
+        self
+    }
+
+    /// Exit codes that mean "no value" for `Option<T>` return types
+    pub fn with_none_codes(mut self, none_codes: impl IntoIterator<Item = i32>) -> Self {
+        self.none_codes = none_codes.into_iter().collect();
+        self
+    }
+
+    /// Trims whitespace around the whole output of scalar values and around every iterator and vector item
+    pub fn with_trim(mut self, trim: bool) -> Self {
+        self.trim = trim;
+        self
+    }
+
+    /// Skips empty and whitespace-only lines
+    pub fn with_skip_empty(mut self, skip_empty: bool) -> Self {
+        self.skip_empty = skip_empty;
+        self
+    }
+
+    /// Removes ANSI escape sequences, e.g. colors
+    pub fn with_strip_ansi(mut self, strip_ansi: bool) -> Self {
+        self.strip_ansi = strip_ansi;
+        self
+    }
+
+    /// Skips lines starting with the given prefix, leading whitespace is ignored
+    pub fn with_skip_comments(mut self, prefix: impl Into<String>) -> Self {
+        self.skip_comments = Some(prefix.into());
+        self
+    }
+
+    pub fn with_parse_from(mut self, parse_from: ParseFrom) -> Self {
+        self.parse_from = parse_from;
+        self
+    }
+
+    /// Path of the Rust function the command is executed for, used as the label of its metrics
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Index of the argument containing the script, which is hashed in the audit log
+    pub fn with_script_arg(mut self, index: usize) -> Self {
+        self.script_arg = Some(index);
+        self
+    }
+
+    /// Names of the environment variables whose values are replaced with `***` in the `Debug` output of `Invocation`,
+    /// in audit records, in forwarded log records and in the output of failed subprocesses
+    pub fn with_secret_envs(mut self, keys: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.secret_envs = keys.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Forwards lines written to the fd from `SHELLFN_LOG_FD` as `<level> <message>` to the `log` facade with the given
+    /// target. Supported only on Unix
+    #[cfg(feature = "log")]
+    pub fn with_log_target(mut self, target: impl Into<String>) -> Self {
+        self.log_target = Some(target.into());
+        self
+    }
+
+    /// Passes lines written to the fd from `SHELLFN_PROGRESS_FD` as `<done> <total> [message]` to the callback.
+    /// Supported only on Unix
+    pub fn with_progress(mut self, callback: impl FnMut(Progress) + Send + 'static) -> Self {
+        self.progress = Some(ProgressCallback::new(callback));
+        self
+    }
+
+    /// Records pid, exit code, duration and output line count of the subprocess in the span, spawn failures and error
+    /// exit codes are recorded as events inside of it
+    #[cfg(feature = "tracing")]
+    pub fn with_span(mut self, span: tracing::Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    pub fn utf8(&self) -> Utf8 {
+        self.utf8
+    }
+
+    pub fn separator(&self) -> Separator {
+        self.separator
+    }
+
+    pub fn none_codes(&self) -> &[i32] {
+        &self.none_codes
+    }
+
+    pub fn trim(&self) -> bool {
+        self.trim
+    }
+
+    pub fn skip_empty(&self) -> bool {
+        self.skip_empty
+    }
+
+    pub fn strip_ansi(&self) -> bool {
+        self.strip_ansi
+    }
+
+    pub fn skip_comments(&self) -> Option<&str> {
+        self.skip_comments.as_deref()
+    }
+
+    pub fn parse_from(&self) -> ParseFrom {
+        self.parse_from
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn script_arg(&self) -> Option<usize> {
+        self.script_arg
+    }
+
+    pub fn secret_envs(&self) -> &[String] {
+        &self.secret_envs
+    }
+
+    pub(crate) fn secrets(&self) -> &Secrets {
+        &self.secrets
+    }
+
+    pub(crate) fn set_secrets(&mut self, secrets: Secrets) {
+        self.secrets = secrets;
+    }
+
+    #[cfg(feature = "log")]
+    pub fn log_target(&self) -> Option<&str> {
+        self.log_target.as_deref()
+    }
+
+    pub fn progress(&self) -> Option<&ProgressCallback> {
+        self.progress.as_ref()
+    }
+
+    #[cfg(feature = "tracing")]
+    pub fn span(&self) -> Option<&tracing::Span> {
+        self.span.as_ref()
+    }
+}
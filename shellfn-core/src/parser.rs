@@ -0,0 +1,42 @@
+use crate::error::Error;
+use crate::options::Options;
+use crate::utils::records;
+use std::io::Read;
+
+/// Parses the whole subprocess output or a single record of it
+///
+/// Implemented for every `FnMut(&str) -> Result<T, E>`, so plain functions can be used as parsers
+pub trait Parser<T> {
+    type Error;
+
+    fn parse(&mut self, input: &str) -> Result<T, Error<Self::Error>>;
+
+    /// Splits the output into records according to `options` and parses every one of them
+    ///
+    /// Formats that define their own records, like CSV, override it
+    fn parse_records<R: Read>(
+        mut self,
+        reader: R,
+        options: Options,
+    ) -> impl Iterator<Item = Result<T, Error<Self::Error>>>
+    where
+        Self: Sized,
+    {
+        records(reader, options).map(move |record| {
+            record
+                .map_err(Error::StdoutUnreadable)
+                .and_then(|record| self.parse(&record))
+        })
+    }
+}
+
+impl<T, E, F> Parser<T> for F
+where
+    F: FnMut(&str) -> Result<T, E>,
+{
+    type Error = E;
+
+    fn parse(&mut self, input: &str) -> Result<T, Error<E>> {
+        self(input).map_err(Error::ParsingError)
+    }
+}
@@ -0,0 +1,173 @@
+use std::fs::File;
+use std::io;
+use std::process::Command;
+
+/// Writing ends of the pipes passed to the child under fixed file descriptors
+///
+/// They have to be kept until the child is spawned and dropped right after that, so reading ends when the child
+/// closes its copies
+#[derive(Default)]
+pub struct ChildFds {
+    writers: Vec<(File, i32)>,
+}
+
+#[cfg(unix)]
+impl ChildFds {
+    /// Opens a pipe with its writing end available as `fd` in the child, returns the reading end
+    pub fn pipe_to_fd(&mut self, fd: i32) -> io::Result<File> {
+        if fd <= 2 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "result fd must not be stdin, stdout or stderr",
+            ));
+        }
+
+        self.pipe(fd)
+    }
+
+    /// Opens a pipe with its writing end available in the child as the lowest fd above stderr not taken by the
+    /// previous pipes, whose number is set to the environment variable `env`, returns the reading end
+    ///
+    /// A single digit fd keeps `>&"$FD"` working in shells (e.g. dash) which do not redirect to higher ones
+    pub fn pipe_to_env(&mut self, command: &mut Command, env: &str) -> io::Result<File> {
+        let mut fd = 3;
+
+        while self.writers.iter().any(|(_, taken)| *taken == fd) {
+            fd += 1;
+        }
+
+        command.env(env, fd.to_string());
+        self.pipe(fd)
+    }
+
+    /// Passes the writing ends to the child spawned by `command`, returns them to be dropped after spawning
+    pub fn pass_to(self, command: &mut Command) -> Vec<File> {
+        use std::os::unix::io::AsRawFd;
+        use std::os::unix::process::CommandExt;
+
+        let mut fds = self
+            .writers
+            .iter()
+            .map(|(writer, child_fd)| (writer.as_raw_fd(), *child_fd))
+            .collect::<Vec<_>>();
+        let above = fds.iter().map(|(_, child_fd)| child_fd + 1).max();
+
+        if let Some(above) = above {
+            // every writer is moved above all the child fds first, so `dup2` does not replace a writer which has
+            // not been passed yet, the moved copies are closed on exec. Nothing is allocated after fork, the moved
+            // fds replace the originals in the child's copy of `fds`
+            unsafe {
+                command.pre_exec(move || {
+                    for (fd, _) in fds.iter_mut() {
+                        *fd = cvt(libc::fcntl(*fd, libc::F_DUPFD_CLOEXEC, above))?;
+                    }
+
+                    for &(fd, child_fd) in &fds {
+                        cvt(libc::dup2(fd, child_fd))?;
+                    }
+
+                    Ok(())
+                });
+            }
+        }
+
+        self.writers.into_iter().map(|(writer, _)| writer).collect()
+    }
+
+    fn pipe(&mut self, child_fd: i32) -> io::Result<File> {
+        let (reader, writer) = pipe()?;
+
+        self.writers.push((writer, child_fd));
+        Ok(reader)
+    }
+}
+
+#[cfg(unix)]
+fn cvt(result: i32) -> io::Result<i32> {
+    if result == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(result)
+    }
+}
+
+// neither end leaks into children, `ChildFds::pass_to` gives the child its own copy. Both ends are opened with `O_CLOEXEC`
+// atomically, so a child spawned meanwhile by another thread does not keep the writer open
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "illumos",
+    target_os = "redox"
+))]
+fn pipe() -> io::Result<(File, File)> {
+    use std::os::unix::io::FromRawFd;
+
+    let mut fds = [0; 2];
+
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) })
+}
+
+// platforms without `pipe2`, e.g. macOS, set `FD_CLOEXEC` right after opening the pipe
+#[cfg(all(
+    unix,
+    not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "netbsd",
+        target_os = "openbsd",
+        target_os = "illumos",
+        target_os = "redox"
+    ))
+))]
+fn pipe() -> io::Result<(File, File)> {
+    use std::os::unix::io::FromRawFd;
+
+    let mut fds = [0; 2];
+
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let pipe = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+
+    for fd in fds {
+        if unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(pipe)
+}
+
+#[cfg(not(unix))]
+impl ChildFds {
+    pub fn pipe_to_fd(&mut self, _fd: i32) -> io::Result<File> {
+        Err(unsupported())
+    }
+
+    pub fn pipe_to_env(&mut self, _command: &mut Command, _env: &str) -> io::Result<File> {
+        Err(unsupported())
+    }
+
+    pub fn pass_to(self, _command: &mut Command) -> Vec<File> {
+        self.writers.into_iter().map(|(writer, _)| writer).collect()
+    }
+}
+
+#[cfg(not(unix))]
+fn unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "passing extra file descriptors is supported only on unix",
+    )
+}
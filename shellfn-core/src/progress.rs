@@ -0,0 +1,71 @@
+use std::fmt::{self, Debug};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// Environment variable with the number of the file descriptor `shellfn_progress` writes to
+pub const PROGRESS_FD_ENV: &str = "SHELLFN_PROGRESS_FD";
+
+/// Progress update reported by the script with `shellfn_progress <done> <total> [message]`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Progress {
+    pub done:    u64,
+    pub total:   u64,
+    pub message: Option<String>,
+}
+
+impl Progress {
+    // `<done> <total> [message]`, other lines are ignored
+    fn parse(line: &str) -> Option<Self> {
+        let mut parts = line.splitn(3, ' ');
+        let done = parts.next()?.parse().ok()?;
+        let total = parts.next()?.parse().ok()?;
+        let message = parts
+            .next()
+            .map(str::trim)
+            .filter(|message| !message.is_empty())
+            .map(str::to_string);
+
+        Some(Self {
+            done,
+            total,
+            message,
+        })
+    }
+}
+
+/// Callback receiving progress updates, called from the thread reading them
+#[derive(Clone)]
+pub struct ProgressCallback(Arc<Mutex<dyn FnMut(Progress) + Send>>);
+
+impl ProgressCallback {
+    pub fn new(callback: impl FnMut(Progress) + Send + 'static) -> Self {
+        Self(Arc::new(Mutex::new(callback)))
+    }
+}
+
+impl Debug for ProgressCallback {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("ProgressCallback")
+    }
+}
+
+/// Reads progress lines written by `shellfn_progress` and passes them to the callback until the subprocess closes
+/// the pipe
+pub fn forward_progress(reader: File, callback: ProgressCallback) -> JoinHandle<()> {
+    thread::spawn(move || {
+        for line in BufReader::new(reader).lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+
+            if let Some(progress) = Progress::parse(&line) {
+                if let Ok(mut callback) = callback.0.lock() {
+                    callback(progress);
+                }
+            }
+        }
+    })
+}
@@ -0,0 +1,94 @@
+use std::borrow::Cow;
+use std::fmt::{self, Debug};
+use std::string::FromUtf8Error;
+
+/// Replacement of secret values in diagnostics
+pub const REDACTED: &str = "***";
+
+/// Values of the environment variables marked as secret, see `Options::with_secret_envs`
+#[derive(Clone, Default)]
+pub struct Secrets {
+    values: Vec<Vec<u8>>,
+}
+
+impl Secrets {
+    /// Empty values are ignored, longer values are replaced first, so a secret containing another one is not
+    /// partially revealed
+    pub fn new(values: impl IntoIterator<Item = Vec<u8>>) -> Self {
+        let mut values = values
+            .into_iter()
+            .filter(|value| !value.is_empty())
+            .collect::<Vec<_>>();
+        values.sort_by_key(|value| std::cmp::Reverse(value.len()));
+
+        Self { values }
+    }
+
+    pub fn redact<'a>(&self, bytes: &'a [u8]) -> Cow<'a, [u8]> {
+        let mut bytes = Cow::Borrowed(bytes);
+
+        for value in &self.values {
+            if contains(&bytes, value) {
+                bytes = Cow::Owned(replace(&bytes, value));
+            }
+        }
+
+        bytes
+    }
+
+    pub fn redact_str<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        match self.redact(text.as_bytes()) {
+            Cow::Borrowed(_) => Cow::Borrowed(text),
+            Cow::Owned(bytes) => Cow::Owned(String::from_utf8_lossy(&bytes).into_owned()),
+        }
+    }
+
+    pub fn redact_in_place(&self, bytes: &mut Vec<u8>) {
+        if let Cow::Owned(redacted) = self.redact(bytes) {
+            *bytes = redacted;
+        }
+    }
+
+    /// Redacts the output carried by the error, if a secret which is not valid UTF-8 was the only invalid part,
+    /// the error carries just a single invalid byte instead
+    pub fn redact_utf8_error(&self, error: FromUtf8Error) -> FromUtf8Error {
+        if self.values.is_empty() {
+            return error;
+        }
+
+        let mut bytes = error.into_bytes();
+        self.redact_in_place(&mut bytes);
+
+        String::from_utf8(bytes)
+            .err()
+            .unwrap_or_else(|| String::from_utf8(vec![0xFF]).unwrap_err())
+    }
+}
+
+// the values are never printed
+impl Debug for Secrets {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Secrets").finish_non_exhaustive()
+    }
+}
+
+fn contains(bytes: &[u8], value: &[u8]) -> bool {
+    bytes.windows(value.len()).any(|window| window == value)
+}
+
+fn replace(bytes: &[u8], value: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut rest = bytes;
+
+    while !rest.is_empty() {
+        if rest.starts_with(value) {
+            result.extend_from_slice(REDACTED.as_bytes());
+            rest = &rest[value.len()..];
+        } else {
+            result.push(rest[0]);
+            rest = &rest[1..];
+        }
+    }
+
+    result
+}
@@ -0,0 +1,101 @@
+use crate::options::Options;
+use std::io;
+use std::process::ExitStatus;
+#[cfg(feature = "tracing")]
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+#[cfg(feature = "tracing")]
+use std::sync::Arc;
+#[cfg(feature = "tracing")]
+use std::time::Instant;
+
+/// Records the subprocess in the span from `Options::with_span`, does nothing without the `tracing` feature
+///
+/// Duration and line count are recorded when the last clone is dropped
+#[derive(Clone, Default)]
+pub struct Trace {
+    #[cfg(feature = "tracing")]
+    inner: Option<Arc<Inner>>,
+}
+
+#[cfg(feature = "tracing")]
+struct Inner {
+    span:    tracing::Span,
+    started: Instant,
+    lines:   AtomicU64,
+    partial: AtomicBool,
+}
+
+#[cfg(feature = "tracing")]
+impl Trace {
+    pub fn new(options: &Options) -> Self {
+        Self {
+            inner: options.span().map(|span| {
+                Arc::new(Inner {
+                    span:    span.clone(),
+                    started: Instant::now(),
+                    lines:   AtomicU64::new(0),
+                    partial: AtomicBool::new(false),
+                })
+            }),
+        }
+    }
+
+    pub fn spawned(&self, pid: u32) {
+        if let Some(ref inner) = self.inner {
+            inner.span.record("pid", pid);
+        }
+    }
+
+    pub fn spawn_failed(&self, error: &io::Error) {
+        if let Some(ref inner) = self.inner {
+            tracing::error!(parent: &inner.span, error = %error, "could not spawn subprocess");
+        }
+    }
+
+    pub fn exited(&self, status: ExitStatus) {
+        if let Some(ref inner) = self.inner {
+            if let Some(code) = status.code() {
+                inner.span.record("exit_code", code);
+            }
+
+            if !status.success() {
+                tracing::warn!(parent: &inner.span, exit_code = status.code(), "subprocess finished with error");
+            }
+        }
+    }
+
+    /// Counts the lines of the next chunk of the output, a last line without `\n` is counted once the output ends
+    pub fn count_lines(&self, chunk: &[u8]) {
+        if let (Some(inner), Some(&last)) = (&self.inner, chunk.last()) {
+            let lines = chunk.iter().filter(|&&byte| byte == b'\n').count();
+            inner.lines.fetch_add(lines as u64, Ordering::Relaxed);
+            inner.partial.store(last != b'\n', Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Drop for Inner {
+    fn drop(&mut self) {
+        self.span
+            .record("duration_ms", self.started.elapsed().as_millis() as u64);
+        let partial = self.partial.load(Ordering::Relaxed) as u64;
+        self.span
+            .record("lines", self.lines.load(Ordering::Relaxed) + partial);
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+impl Trace {
+    pub fn new(_options: &Options) -> Self {
+        Self {}
+    }
+
+    pub fn spawned(&self, _pid: u32) {}
+
+    pub fn spawn_failed(&self, _error: &io::Error) {}
+
+    pub fn exited(&self, _status: ExitStatus) {}
+
+    pub fn count_lines(&self, _chunk: &[u8]) {}
+}
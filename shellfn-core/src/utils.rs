@@ -1,29 +1,433 @@
+#[cfg(feature = "audit")]
+use crate::audit::Audit;
 use crate::error::Error;
-use std::error::Error as StdError;
+use crate::hooks::{run_after_exit, run_before_spawn, Invocation, Outcome};
+#[cfg(feature = "log")]
+use crate::log_bridge::{forward_records, LOG_FD_ENV};
+use crate::metrics::Metrics;
+use crate::options::{Options, ParseFrom, Separator, Utf8};
+use crate::pipe::ChildFds;
+use crate::progress::{forward_progress, PROGRESS_FD_ENV};
+use crate::trace::Trace;
 use std::ffi::OsStr;
-use std::io;
-use std::process::{Child, Command, Stdio};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::panic;
+use std::process::{Child, ChildStderr, ChildStdout, Command, ExitStatus, Output, Stdio};
+use std::string::FromUtf8Error;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
 
 pub const PANIC_MSG: &str = "Shell execution failed";
+const DETACHED_MSG: &str = "process is waited for by a background thread";
 
 pub fn spawn<TArg, TEnvKey, TEnvVal>(
     cmd: impl AsRef<OsStr>,
     args: impl IntoIterator<Item = TArg>,
     envs: impl IntoIterator<Item = (TEnvKey, TEnvVal)>,
-) -> Result<Child, io::Error>
+    options: &mut Options,
+) -> Result<Process, io::Error>
 where
     TArg: AsRef<OsStr>,
     TEnvKey: AsRef<OsStr>,
     TEnvVal: AsRef<OsStr>,
 {
-    Command::new(cmd)
-        .stdout(Stdio::piped())
-        .args(args)
-        .envs(envs)
-        .spawn()
+    let trace = Trace::new(options);
+    let metrics = Metrics::new(options);
+    #[cfg(feature = "audit")]
+    let audit = Audit::new(options);
+    let mut invocation = Invocation::new(options.name(), cmd, args, envs)
+        .with_secret_envs(options.secret_envs().iter().map(String::as_str));
+    let spawned =
+        run_before_spawn(&mut invocation).and_then(|_| spawn_untraced(&invocation, options));
+
+    // the output is decoded with the same options, so errors carrying it can be redacted
+    options.set_secrets(invocation.secrets());
+
+    match spawned {
+        Ok((child, result, forwarders)) => {
+            trace.spawned(child.id());
+
+            Ok(Process {
+                child: Some(child),
+                result,
+                forwarders,
+                trace,
+                metrics,
+                #[cfg(feature = "audit")]
+                audit,
+                invocation,
+                exited: false,
+            })
+        }
+        Err(e) => {
+            trace.spawn_failed(&e);
+            metrics.spawn_failed();
+            #[cfg(feature = "audit")]
+            audit.record(&invocation, &Outcome::SpawnFailed(&e));
+            run_after_exit(&invocation, &Outcome::SpawnFailed(&e));
+            Err(e)
+        }
+    }
+}
+
+type Spawned = (Child, Option<File>, Vec<JoinHandle<()>>);
+
+fn spawn_untraced(invocation: &Invocation, options: &Options) -> io::Result<Spawned> {
+    let mut command = Command::new(invocation.cmd());
+    let mut result = None;
+    let mut fds = ChildFds::default();
+    let mut forwarders = Vec::new();
+
+    match options.parse_from() {
+        ParseFrom::Stdout => {
+            command.stdout(Stdio::piped());
+        }
+        ParseFrom::Stderr => {
+            command.stderr(Stdio::piped());
+        }
+        ParseFrom::Both => {
+            command.stdout(Stdio::piped()).stderr(Stdio::piped());
+        }
+        ParseFrom::Fd(fd) => {
+            result = Some(fds.pipe_to_fd(fd)?);
+        }
+    }
+
+    #[cfg(feature = "log")]
+    if let Some(target) = options.log_target() {
+        let reader = fds.pipe_to_env(&mut command, LOG_FD_ENV)?;
+        forwarders.push(forward_records(
+            reader,
+            target.to_string(),
+            invocation.secrets(),
+        ));
+    }
+
+    if let Some(callback) = options.progress() {
+        let reader = fds.pipe_to_env(&mut command, PROGRESS_FD_ENV)?;
+        forwarders.push(forward_progress(reader, callback.clone()));
+    }
+
+    let writers = fds.pass_to(&mut command);
+    let child = command
+        .args(invocation.args())
+        .envs(invocation.envs().iter().map(|(key, val)| (key, val)))
+        .spawn()?;
+
+    // the writing ends are dropped right after spawning, so the reading ends when the child closes its copies
+    drop(writers);
+
+    Ok((child, result, forwarders))
+}
+
+/// Spawned child process with the reading end of the `ParseFrom::Fd` pipe, the threads forwarding its log
+/// records and progress updates, its `tracing` span, its metrics and the invocation passed to the hooks
+pub struct Process {
+    // taken only by a background thread waiting for the process after it was dropped
+    child:      Option<Child>,
+    result:     Option<File>,
+    forwarders: Vec<JoinHandle<()>>,
+    trace:      Trace,
+    metrics:    Metrics,
+    #[cfg(feature = "audit")]
+    audit:      Audit,
+    invocation: Invocation,
+    exited:     bool,
+}
+
+impl Process {
+    /// Same as `Child::wait`, but also waits until every log record and progress update of the process is forwarded
+    pub fn wait(&mut self) -> io::Result<ExitStatus> {
+        let status = self.deref_mut().wait()?;
+
+        // the exit is reported once, also when the process is waited for again or dropped
+        if !self.exited {
+            self.exited = true;
+            join(self.forwarders.drain(..));
+            self.trace.exited(status);
+            self.metrics.exited(status);
+            #[cfg(feature = "audit")]
+            self.audit
+                .record(&self.invocation, &Outcome::Exited(status));
+            run_after_exit(&self.invocation, &Outcome::Exited(status));
+        }
+
+        Ok(status)
+    }
+
+    /// Same as `Child::wait_with_output`, but also waits until every log record and progress update of the process
+    /// is forwarded
+    fn wait_with_output(mut self) -> io::Result<Output> {
+        // stderr is read by another thread, so the child does not block on one pipe while the other one is read
+        let stderr = self
+            .stderr
+            .take()
+            .map(|stderr| thread::spawn(move || read_all(stderr)));
+        let stdout = self.stdout.take().map(read_all).transpose()?;
+        let stderr = stderr
+            .map(|reader| reader.join().unwrap_or_else(|e| panic::resume_unwind(e)))
+            .transpose()?;
+        let mut output = Output {
+            status: self.wait()?,
+            stdout: stdout.unwrap_or_default(),
+            stderr: stderr.unwrap_or_default(),
+        };
+
+        // the output of a failed subprocess only ends up in errors
+        if !output.status.success() {
+            let secrets = self.invocation.secrets();
+            secrets.redact_in_place(&mut output.stdout);
+            secrets.redact_in_place(&mut output.stderr);
+        }
+
+        Ok(output)
+    }
+
+    pub fn trace(&self) -> &Trace {
+        &self.trace
+    }
+
+    // moves the child and the reporting of its exit to a new process, this one is then treated as exited
+    fn detach(&mut self) -> Process {
+        self.exited = true;
+
+        Process {
+            child:                           self.child.take(),
+            result:                          None,
+            forwarders:                      mem::take(&mut self.forwarders),
+            trace:                           self.trace.clone(),
+            metrics:                         self.metrics.clone(),
+            #[cfg(feature = "audit")]
+            audit:                           self.audit.clone(),
+            invocation:                      self.invocation.clone(),
+            exited:                          false,
+        }
+    }
+}
+
+// a process dropped before it is waited for, e.g. by an iterator which is not exhausted, does not block the caller.
+// Its pipes are closed, and its exit is reported right away if the child is done already, or by a background thread
+// waiting for it otherwise
+impl Drop for Process {
+    fn drop(&mut self) {
+        if self.exited {
+            return;
+        }
+
+        drop(self.result.take());
+        drop(self.stdout.take());
+        drop(self.stderr.take());
+
+        if let Ok(None) = self.try_wait() {
+            let mut running = self.detach();
+
+            thread::spawn(move || {
+                let _ = running.wait();
+            });
+        } else {
+            let _ = self.wait();
+        }
+    }
+}
+
+fn read_all(mut reader: impl Read) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    reader.read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+// the threads end once the child and its subprocesses close the side channel fds
+fn join(forwarders: impl IntoIterator<Item = JoinHandle<()>>) {
+    for forwarder in forwarders {
+        let _ = forwarder.join();
+    }
+}
+
+impl Deref for Process {
+    type Target = Child;
+
+    fn deref(&self) -> &Child {
+        self.child.as_ref().expect(DETACHED_MSG)
+    }
+}
+
+impl DerefMut for Process {
+    fn deref_mut(&mut self) -> &mut Child {
+        self.child.as_mut().expect(DETACHED_MSG)
+    }
+}
+
+/// Takes the stream selected with `ParseFrom` from the spawned process
+pub fn take_output(process: &mut Process, parse_from: ParseFrom) -> ChildOutput {
+    let stream = match parse_from {
+        ParseFrom::Stdout => OutputStream::Stdout(process.stdout.take().unwrap()),
+        ParseFrom::Stderr => OutputStream::Stderr(process.stderr.take().unwrap()),
+        ParseFrom::Both => OutputStream::Both(MergedOutput::new(
+            process.stdout.take().unwrap(),
+            process.stderr.take().unwrap(),
+        )),
+        ParseFrom::Fd(_) => OutputStream::Fd(process.result.take().unwrap()),
+    };
+
+    ChildOutput {
+        stream,
+        trace: process.trace.clone(),
+    }
+}
+
+/// Same as `Child::wait_with_output`, but the interleaved output of `ParseFrom::Both` and the output written to
+/// `ParseFrom::Fd` are collected into `stdout`
+pub fn wait_with_output(mut process: Process, parse_from: ParseFrom) -> io::Result<Output> {
+    if let ParseFrom::Stdout | ParseFrom::Stderr = parse_from {
+        let trace = process.trace.clone();
+        let output = process.wait_with_output()?;
+
+        match parse_from {
+            ParseFrom::Stderr => trace.count_lines(&output.stderr),
+            _ => trace.count_lines(&output.stdout),
+        }
+
+        return Ok(output);
+    }
+
+    let mut stdout = Vec::new();
+    take_output(&mut process, parse_from).read_to_end(&mut stdout)?;
+    let status = process.wait()?;
+
+    if !status.success() {
+        process.invocation.secrets().redact_in_place(&mut stdout);
+    }
+
+    Ok(Output {
+        status,
+        stdout,
+        stderr: Vec::new(),
+    })
+}
+
+/// Returns the part of the output collected with `wait_with_output` that should be parsed
+pub fn parsed_output(output: Output, parse_from: ParseFrom) -> Vec<u8> {
+    match parse_from {
+        ParseFrom::Stdout | ParseFrom::Both | ParseFrom::Fd(_) => output.stdout,
+        ParseFrom::Stderr => output.stderr,
+    }
+}
+
+/// Stream selected with `ParseFrom`, every line read from it is counted in the `tracing` span of the process
+pub struct ChildOutput {
+    stream: OutputStream,
+    trace:  Trace,
+}
+
+enum OutputStream {
+    Stdout(ChildStdout),
+    Stderr(ChildStderr),
+    Both(MergedOutput),
+    Fd(File),
+}
+
+impl Read for ChildOutput {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = match self.stream {
+            OutputStream::Stdout(ref mut stdout) => stdout.read(buf)?,
+            OutputStream::Stderr(ref mut stderr) => stderr.read(buf)?,
+            OutputStream::Both(ref mut merged) => merged.read(buf)?,
+            OutputStream::Fd(ref mut file) => file.read(buf)?,
+        };
+
+        self.trace.count_lines(&buf[..len]);
+
+        Ok(len)
+    }
 }
 
-pub fn check_exit_code<E: StdError>(process: Child) -> Result<(), Error<E>> {
+/// Stdout and stderr of the process interleaved line by line, see `merged_lines`
+pub struct MergedOutput {
+    lines:    Receiver<(Stream, io::Result<Vec<u8>>)>,
+    line:     Vec<u8>,
+    position: usize,
+}
+
+impl MergedOutput {
+    fn new(stdout: ChildStdout, stderr: ChildStderr) -> Self {
+        Self {
+            lines:    merged_lines(stdout, stderr),
+            line:     Vec::new(),
+            position: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// Lines of stdout and stderr of the process in the order they are written, every stream is read by its own thread
+///
+/// Lines keep their line endings, the receiver is disconnected once both streams are closed
+pub fn merged_lines(
+    stdout: ChildStdout,
+    stderr: ChildStderr,
+) -> Receiver<(Stream, io::Result<Vec<u8>>)> {
+    let (sender, lines) = mpsc::channel();
+
+    forward_lines(stdout, Stream::Stdout, sender.clone());
+    forward_lines(stderr, Stream::Stderr, sender);
+
+    lines
+}
+
+fn forward_lines<R: Read + Send + 'static>(
+    reader: R,
+    stream: Stream,
+    sender: Sender<(Stream, io::Result<Vec<u8>>)>,
+) {
+    thread::spawn(move || {
+        let mut reader = BufReader::new(reader);
+
+        loop {
+            let mut line = Vec::new();
+
+            match reader.read_until(b'\n', &mut line) {
+                Ok(0) => break,
+                Ok(_) if sender.send((stream, Ok(line))).is_ok() => {}
+                Ok(_) => break,
+                Err(e) => {
+                    let _ = sender.send((stream, Err(e)));
+                    break;
+                }
+            }
+        }
+    });
+}
+
+impl Read for MergedOutput {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.line.len() {
+            match self.lines.recv() {
+                Ok((_, line)) => {
+                    self.line = line?;
+                    self.position = 0;
+                }
+                // both threads finished
+                Err(_) => return Ok(0),
+            }
+        }
+
+        let len = buf.len().min(self.line.len() - self.position);
+        buf[..len].copy_from_slice(&self.line[self.position..self.position + len]);
+        self.position += len;
+
+        Ok(len)
+    }
+}
+
+pub fn check_exit_code<E>(process: Process) -> Result<(), Error<E>> {
     let output = process.wait_with_output().map_err(Error::WaitFailed)?;
 
     if !output.status.success() {
@@ -33,10 +437,217 @@ pub fn check_exit_code<E: StdError>(process: Child) -> Result<(), Error<E>> {
     }
 }
 
-pub fn check_exit_code_panic(process: Child) {
+pub fn check_exit_code_panic(process: Process) {
     let output = process.wait_with_output().expect(PANIC_MSG);
 
     if !output.status.success() {
         panic!("{}", PANIC_MSG)
     }
 }
+
+pub fn decode(bytes: Vec<u8>, options: &Options) -> Result<String, FromUtf8Error> {
+    match options.utf8() {
+        Utf8::Strict => {
+            String::from_utf8(bytes).map_err(|e| options.secrets().redact_utf8_error(e))
+        }
+        Utf8::Lossy => Ok(String::from_utf8_lossy(&bytes).into_owned()),
+    }
+}
+
+/// Decodes the whole output of scalar values and normalizes it according to `Options`
+pub fn decode_output(bytes: Vec<u8>, options: &Options) -> Result<String, FromUtf8Error> {
+    decode(bytes, options).map(|output| normalize(output, options).unwrap_or_default())
+}
+
+/// Strips ANSI escape sequences, filters out lines and trims according to `Options`
+///
+/// Returns `None` when every line was filtered out
+pub fn normalize(text: String, options: &Options) -> Option<String> {
+    let mut text = if options.strip_ansi() {
+        strip_ansi(&text)
+    } else {
+        text
+    };
+
+    if options.skip_empty() || options.skip_comments().is_some() {
+        if text.is_empty() && options.skip_empty() {
+            return None;
+        }
+
+        if !text.is_empty() {
+            let lines = text
+                .lines()
+                .filter(|line| !is_skipped(line, options))
+                .collect::<Vec<_>>();
+
+            if lines.is_empty() {
+                return None;
+            }
+
+            text = lines.join("\n");
+        }
+    }
+
+    if options.trim() {
+        text = text.trim().to_string();
+    }
+
+    Some(text)
+}
+
+fn is_skipped(line: &str, options: &Options) -> bool {
+    let line = line.trim_start();
+
+    (options.skip_empty() && line.is_empty())
+        || options
+            .skip_comments()
+            .is_some_and(|prefix| line.starts_with(prefix))
+}
+
+// removes CSI sequences (e.g. colors, `ESC [ 31 m`), OSC sequences (e.g. hyperlinks, terminated with BEL or `ESC \\`)
+// and other two-character escape sequences
+fn strip_ansi(text: &str) -> String {
+    const ESC: char = '\u{1b}';
+    const BEL: char = '\u{7}';
+
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != ESC {
+            result.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('\u{40}'..='\u{7e}').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == BEL || (c == ESC && chars.next_if_eq(&'\\').is_some()) {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    result
+}
+
+pub fn records<R: Read>(reader: R, options: Options) -> Records<BufReader<R>> {
+    Records {
+        reader: BufReader::new(reader),
+        options,
+        lines: 0,
+        start: 0,
+    }
+}
+
+/// Same as `std::io::Lines`, but splits on `Options::separator`, decodes every record according to `Options::utf8`
+/// and normalizes it, skipping records that are filtered out
+pub struct Records<R> {
+    reader:  R,
+    options: Options,
+    // lines read so far and the line the last record starts at
+    lines:   usize,
+    start:   usize,
+}
+
+impl<R> Records<R> {
+    /// Number of the output line (counted from 1) the last record starts at, skipped records and lines included
+    #[cfg_attr(not(feature = "serde"), allow(dead_code))]
+    pub fn line(&self) -> usize {
+        self.start
+    }
+}
+
+impl<R: BufRead> Records<R> {
+    fn read_line(&mut self) -> io::Result<Option<Vec<u8>>> {
+        self.read_until(b'\n').map(|line| {
+            line.map(|mut line| {
+                if line.ends_with(b"\r") {
+                    line.pop();
+                }
+                line
+            })
+        })
+    }
+
+    fn read_until(&mut self, separator: u8) -> io::Result<Option<Vec<u8>>> {
+        let mut buf = Vec::new();
+
+        if self.reader.read_until(separator, &mut buf)? == 0 {
+            return Ok(None);
+        }
+
+        self.start = self.lines + 1;
+        self.lines += buf.iter().filter(|&&byte| byte == b'\n').count();
+
+        if buf.last() == Some(&separator) {
+            buf.pop();
+        }
+
+        Ok(Some(buf))
+    }
+
+    fn read_paragraph(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut paragraph: Option<Vec<u8>> = None;
+        let mut start = self.start;
+
+        while let Some(line) = self.read_line()? {
+            let is_blank = line.iter().all(u8::is_ascii_whitespace);
+
+            match paragraph {
+                Some(_) if is_blank => break,
+                Some(ref mut paragraph) => {
+                    paragraph.push(b'\n');
+                    paragraph.extend(line);
+                }
+                None if is_blank => {}
+                None => {
+                    start = self.start;
+                    paragraph = Some(line);
+                }
+            }
+        }
+
+        self.start = start;
+        Ok(paragraph)
+    }
+}
+
+impl<R: BufRead> Iterator for Records<R> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let record = match self.options.separator() {
+                Separator::Newline => self.read_line(),
+                Separator::Byte(separator) => self.read_until(separator),
+                Separator::Paragraph => self.read_paragraph(),
+            };
+
+            let record = match record {
+                Ok(Some(record)) => decode(record, &self.options)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+                Ok(None) => return None,
+                Err(e) => Err(e),
+            };
+
+            match record {
+                Ok(record) => match normalize(record, &self.options) {
+                    Some(record) => return Some(Ok(record)),
+                    None => continue,
+                },
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
@@ -53,10 +53,11 @@
 //! - return a value that is either `void`, `T`, `Result<T, E>`, `impl Iterator<Item=T>`, `Result<impl Iterator<Item=T>>` or `Result<impl Iterator<Item=Result<T, E>>>` with constrains:
 //! ```
 //! T: FromStr,
-//! <T as FromStr>::Err: StdError,
 //! E: From<shellfn::Error<<T as FromStr>::Err>>,
 //! ```
 //!
+//! `shellfn::Error<PE>` implements `std::error::Error` only when `PE` does, so e.g. `Box<dyn Error>` requires `<T as FromStr>::Err: StdError`.
+//!
 //! - ## Details
 //!
 //! The `#[shell]` attribute does the following:
@@ -84,6 +85,147 @@
 //! ```rust
 //! #[shell(no_panic)]
 //! ```
+//! - by default, the output must be valid UTF-8. You can replace invalid sequences with `U+FFFD` instead of failing using the `utf8` parameter:
+//! ```rust
+//! #[shell(utf8 = "lossy")]
+//! ```
+//! - iterators and vectors are parsed line by line. You can split the output on a different byte or on blank lines using the `separator` parameter:
+//! ```rust
+//! #[shell(separator = "\0")]
+//! #[shell(separator = "paragraph")]
+//! ```
+//! - the output is parsed as it is, so e.g. `echo 42` cannot be parsed as `u32` because of the trailing newline. The `trim` flag trims whitespace around single values and every iterator and vector item. Lines can be filtered out with the `skip_empty` flag and the `skip_comments` parameter, and ANSI escape sequences (e.g. colors) can be removed with the `strip_ansi` flag. These options apply to every return type except raw output and `format = "csv"`:
+//! ```rust
+//! #[shell(trim, skip_empty, strip_ansi, skip_comments = "#")]
+//! ```
+//! - the result is parsed from stdout of the script, stderr is inherited. Tools that report to stderr can be parsed with `parse_from = "stderr"` (stdout is inherited then), and `parse_from = "both"` interleaves both streams line by line in the order the lines are written. It does not apply to `()` and raw output:
+//! ```rust
+//! #[shell(parse_from = "stderr")]
+//! #[shell(parse_from = "both")]
+//! ```
+//! - scripts that print progress or logs can write the result to a dedicated file descriptor instead (Unix only). With `result_fd = 3` only what the script writes to fd 3 (e.g. `echo 42 >&3`) is parsed, and both stdout and stderr are inherited:
+//! ```rust
+//! #[shell(result_fd = 3)]
+//! ```
+//! - to tell the streams apart, return an iterator of `shellfn::ShellEvent<T>`. Both streams are read concurrently and their lines are yielded in the order they are written: stdout lines parsed as `ShellEvent::Stdout(T)` and stderr lines as `ShellEvent::Stderr(String)`. The last event is `ShellEvent::Exit` with the exit status, so failed scripts do not panic:
+//! ```rust
+//! #[shell]
+//! fn build() -> impl Iterator<Item = ShellEvent<String>> {
+//!     "cargo build"
+//! }
+//! ```
+//! - with the `log` cargo feature enabled, the `log_bridge` flag defines the `shellfn_log <level> <message>` shell function for scripts run by `sh`, `bash`, `dash`, `ash`, `ksh`, `mksh` or `zsh` (Unix only), scripts in other languages write the same lines to the file descriptor in `SHELLFN_LOG_FD`. Every call becomes a record of the `log` facade with the path of the Rust function as target, e.g. `my_crate::cleanup`. Levels are `error`, `warn`, `info`, `debug` and `trace`, lines without a known level are logged as `info`:
+//! ```rust
+//! #[shell(log_bridge)]
+//! fn cleanup() {
+//!     r#"
+//!     shellfn_log warn "disk almost full"
+//!     rm -rf /tmp/cache
+//!     "#
+//! }
+//! ```
+//! - long-running scripts can report progress with `shellfn_progress <done> <total> [message]` when the function has a parameter marked with `#[progress]` (Unix only), scripts in other languages than the shells listed above write the same lines to the file descriptor in `SHELLFN_PROGRESS_FD`. Every update is passed to the callback as `shellfn::Progress` from a background thread, so it has to be `Send + 'static`. Progress goes over a separate file descriptor, the lowest one above stderr not taken by `result_fd` or the log records, so parsing of the output is not affected:
+//! ```rust
+//! #[shell]
+//! fn migrate(#[progress] progress: impl FnMut(Progress) + Send + 'static) {
+//!     r#"
+//!     shellfn_progress 1 2 users
+//!     shellfn_progress 2 2 orders
+//!     "#
+//! }
+//! ```
+//! - with the `tracing` cargo feature enabled, every call opens an `info` span named after the Rust function. It has the `interpreter` and the names (not values) of the passed `envs` as fields and records `pid`, `exit_code`, `duration_ms` and the number of parsed output `lines`. Spawn failures are recorded as `error` events and non-zero exit codes as `warn` events inside of the span
+//! - with the `metrics` cargo feature enabled, every call is recorded in the `metrics` facade with the path of the Rust function as the `function` label: the `shellfn_invocations_total`, `shellfn_spawn_failures_total`, `shellfn_failed_exits_total` and `shellfn_parse_failures_total` counters and the `shellfn_duration_seconds` histogram
+//! - process-global hooks registered with `shellfn::before_spawn` run before every subprocess is spawned and can modify the command, its args and environment variables or veto the call. A vetoed call fails like a subprocess that could not be spawned. Hooks registered with `shellfn::after_exit` receive the exit status, or the spawn error. Dropping an iterator before its end does not wait for the subprocess, its exit is then reported from a background thread:
+//! ```rust
+//! shellfn::before_spawn(|invocation| {
+//!     if read_only() && invocation.name() == Some("my_crate::cleanup") {
+//!         return Err(shellfn::Veto::new("read-only mode"));
+//!     }
+//!
+//!     invocation.set_env("CORRELATION_ID", correlation_id());
+//!     Ok(())
+//! });
+//! ```
+//! - with the `audit` cargo feature enabled, every invocation can be appended as a JSON object to a [JSON Lines](https://jsonlines.org) file, set with the `SHELLFN_AUDIT_LOG` environment variable or with `shellfn::set_audit_log(Some(path))`. A record contains the `timestamp`, the `function` path, the `interpreter`, the `argv` with the script replaced by its SHA-256 hash, the names (not values) of the `envs`, the `cwd`, the `user`, the `exit_code` (or the spawn `error`) and the `duration_ms`
+//! - parameters marked with `#[secret]` are still passed to the script, but their values are replaced with `***` in the `Debug` output of `shellfn::Invocation`, in audit records, in records forwarded by the `log_bridge` in the output of failed subprocesses kept in `shellfn::Error::ProcessFailed` and in the output kept in `shellfn::Error::NonUtf8Stdout`. Tracing spans only contain the names of environment variables. Errors of parsers (`shellfn::Error::ParsingError` and `shellfn::Error::JsonParsingError`) are not redacted, they may quote the output, so a script should not print secrets in its parsed output:
+//! ```rust
+//! #[shell]
+//! fn login(user: &str, #[secret] token: &str) -> Result<String, Box<Error>> {
+//!     r#"curl -s -u "$USER:$(printenv TOKEN)" https://example.com/login"#
+//! }
+//! ```
+//! - the output is parsed using `FromStr`. You can use any `fn(&str) -> Result<T, E>` instead, either for the whole output (`parse_with`) or for every iterator and vector item (`parse_lines_with`):
+//! ```rust
+//! #[shell(parse_with = path::to::parse_fn)]
+//! #[shell(parse_lines_with = path::to::parse_line_fn)]
+//! ```
+//! - if the parsing error does not implement `std::error::Error` (e.g. `type Err = String`), you can wrap it in `shellfn::DebugError` using the `wrap_parse_error` flag:
+//! ```rust
+//! #[shell(wrap_parse_error)]
+//! ```
+//! - tuples like `(String, u32)` can be used as values and as iterator or vector items even though they do not implement `FromStr`. Every record is split into columns on whitespace, or on the character given in the `delimiter` parameter, and each column is parsed with its own `FromStr`. Errors are reported as `shellfn::ColumnError` with the column index:
+//! ```rust
+//! #[shell(delimiter = "\t")]
+//! ```
+//! - for anything more complex than tuples, `#[derive(FromShellLine)]` generates a `FromStr` implementation that works with every return type. Fields are bound to columns (in field order, or with `column = N`), to named capture groups of a `regex` (requires the `regex` cargo feature) or to `key_value` tokens like `name=value`. Extra columns and unknown keys are ignored. Errors are reported as `shellfn::LineError` with the field name:
+//! ```rust
+//! #[derive(FromShellLine)]
+//! #[shell_line(delimiter = ':')]
+//! struct Passwd {
+//!     name: String,
+//!     #[shell_line(column = 2)]
+//!     uid: u32,
+//! }
+//!
+//! #[derive(FromShellLine)]
+//! #[shell_line(regex = r"^(?P<hash>\w+) (?P<subject>.*)$")]
+//! struct Commit {
+//!     hash: String,
+//!     subject: String,
+//! }
+//!
+//! #[derive(FromShellLine)]
+//! #[shell_line(key_value)]
+//! struct Mount {
+//!     #[shell_line(key = "TARGET")]
+//!     target: String,
+//! }
+//! ```
+//! - `HashMap<K, V>`, `BTreeMap<K, V>` and `IndexMap<K, V>` (requires the `indexmap` cargo feature) can be returned from `key=value` lines, also wrapped with `Result`. Every line is split on the first `=`, or on the `key_value_separator`, both sides are trimmed and parsed with `FromStr`. Duplicate keys keep the last value, unless `duplicate_keys` is `"first"` or `"error"`. Errors are reported as `shellfn::KeyValueError`:
+//! ```rust
+//! #[shell(key_value_separator = ":", duplicate_keys = "error")]
+//! ```
+//! - everything that applies to `Vec<T>` also applies to `VecDeque`, `LinkedList`, `HashSet`, `BTreeSet`, `BinaryHeap` and `SmallVec`. Any other `FromIterator<T>` collection can be used with the `collect` flag. Maps are parsed from `key=value` lines with or without the flag:
+//! ```rust
+//! #[shell(collect)]
+//! ```
+//! - `Box<dyn Iterator<Item=T>>` and `impl IntoIterator<Item=T>` can be used in place of `impl Iterator<Item=T>`. The shape of the return type is recognized by the names of the types, so e.g. type aliases like `type Res<T> = Result<T, MyError>` are not recognized. You can state it explicitly using the `returns` parameter (`"iter"`, `"vec"`, `"value"` or `"unit"`) and the `fallible` flag for return types wrapped with `Result`:
+//! ```rust
+//! #[shell(returns = "vec", fallible)]
+//! fn list() -> Res<Vec<String>>
+//! ```
+//! - `Option<T>` return types give `None` for empty output. You can also list exit codes that mean "no value" in the `none_codes` parameter, e.g. for `grep`:
+//! ```rust
+//! #[shell(none_codes = [1])]
+//! ```
+//! - with the `serde` cargo feature enabled, the output can be deserialized from JSON (`T: DeserializeOwned`) instead of parsed with `FromStr`. Errors are reported as `shellfn::Error::JsonParsingError` with the line and column:
+//! ```rust
+//! #[shell(format = "json")]
+//! ```
+//! - similarly, every line of iterators and vectors can be deserialized from JSON Lines. A bad record produces an item error with its line number:
+//! ```rust
+//! #[shell(format = "jsonl")]
+//! ```
+//! - with the `csv` cargo feature enabled, iterators and vectors can be deserialized from CSV or TSV records. Columns are mapped by the header row, or by position with the `no_headers` flag. Quoted fields may contain separators and newlines:
+//! ```rust
+//! #[shell(format = "csv")]
+//! #[shell(format = "tsv", no_headers)]
+//! ```
+//! - column-aligned tables with a header line, like the output of `df`, `ps aux` or `kubectl get pods`, can be deserialized with `format = "table"` (also behind the `csv` cargo feature). Column boundaries are inferred from the positions that are blank in every line and the last column spans to the end of the line, so values may contain spaces. Columns are mapped by the header names. Iterators yield their rows only after the whole output is read, as every line is needed to find the columns:
+//! ```rust
+//! #[shell(format = "table")]
+//! ```
 //!
 //! Following return types are currently recognized:
 //!
@@ -99,6 +241,14 @@
 //! | T                                             | no_panic | panic         | panic              | panic         | 1,2   |
 //! | Result<T, E>                                  |          | error         | error              | error         | 2     |
 //! | Result<T, E>                                  | no_panic | error         | error              | error         | 1,2   |
+//! | Option<T>                                     |          | panic         | panic              | panic         | 2,5   |
+//! | Option<T>                                     | no_panic | panic         | panic              | panic         | 1,2,5 |
+//! | Result<Option<T>, E>                          |          | error         | error              | error         | 2,5   |
+//! | Result<Option<T>, E>                          | no_panic | error         | error              | error         | 1,2,5 |
+//! | Vec<u8>                                       |          | -             | panic              | panic         | 4     |
+//! | Vec<u8>                                       | no_panic | -             | panic              | panic         | 1,4   |
+//! | Result<Vec<u8>, E>                            |          | -             | error              | error         | 4     |
+//! | Result<Vec<u8>, E>                            | no_panic | -             | error              | error         | 1,4   |
 //! | Vec<T>                                        |          | panic         | panic              | panic         |       |
 //! | Vec<T>                                        | no_panic | skip          | ignored            | empty vec     | 3     |
 //! | Vec<Result<T, E>>                             |          | item error    | panic              | panic         |       |
@@ -115,6 +265,8 @@
 //! | Result<impl Iterator<Item=T>, E>              | no_panic | skip          | ignored            | error         |       |
 //! | Result<impl Iterator<Item=Result<T, E1>>, E2> |          | item error    | ignored            | error         |       |
 //! | Result<impl Iterator<Item=Result<T, E1>>, E2> | no_panic | item error    | ignored            | error         | 1     |
+//! | impl Iterator<Item=ShellEvent<T>>             |          | panic         | exit event         | panic         | 6     |
+//! | Result<impl Iterator<Item=ShellEvent<T>>, E>  |          | panic         | exit event         | error         | 6     |
 //!
 //! Glossary:
 //!
@@ -127,12 +279,16 @@
 //! | empty iter/vec | returns empty iterator / vector                                            |
 //! | item error     | when parsing fails, yields Err                                             |
 //! | ignored        | ignores exit code, behaves in the same way for exit code 0 and != 0        |
+//! | exit event     | yields `ShellEvent::Exit` with the exit status as the last item            |
 //!
 //! Notes:
 //!
 //! 1. The `no_panic` attribute makes no difference
 //! 2. It reads all of stdout before producing any failures
 //! 3. It yields all items until it encounters an error or an exit code
+//! 4. It returns raw stdout without decoding, the same applies to `bytes::Bytes`
+//! 5. It returns `None` when stdout is empty or whitespace-only, or when the exit code is listed in `none_codes`
+//! 6. It yields stdout and stderr lines in the order they are written. Items can be wrapped with `Result` (`Result<ShellEvent<T>, E>`) to yield parsing failures as item errors, `no_panic` cannot be used
 //!
 //! ### Vector vs iterator
 //!
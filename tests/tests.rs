@@ -83,6 +83,2530 @@ fn parses_return_value() {
     assert_eq!(4224, join(42, 24));
 }
 
+mod decodes_utf8 {
+    use super::*;
+
+    mod when_it_is_strict {
+        use super::*;
+
+        #[shell]
+        fn value() -> Result<String, BoxedError> {
+            r#"
+            printf 'a\xffb'
+            "#
+        }
+
+        #[shell]
+        fn lines() -> Vec<Result<String, BoxedError>> {
+            r#"
+            printf 'a\xffb\nc\n'
+            "#
+        }
+
+        #[test]
+        fn returns_error_for_invalid_value() {
+            assert!(value().is_err())
+        }
+
+        #[test]
+        fn returns_item_error_for_invalid_line() {
+            let actual = lines();
+
+            assert_eq!(2, actual.len());
+            assert!(actual[0].is_err());
+            assert_eq!("c", actual[1].as_ref().unwrap());
+        }
+    }
+
+    mod when_it_is_lossy {
+        use super::*;
+
+        #[shell(utf8 = "lossy")]
+        fn value() -> String {
+            r#"
+            printf 'a\xffb'
+            "#
+        }
+
+        #[shell(utf8 = "lossy")]
+        fn iter() -> impl Iterator<Item = String> {
+            r#"
+            printf 'a\xffb\nc\r\n'
+            "#
+        }
+
+        #[shell(utf8 = "lossy")]
+        fn vec() -> Vec<String> {
+            r#"
+            printf 'a\xffb\nc'
+            "#
+        }
+
+        #[test]
+        fn replaces_invalid_characters_in_value() {
+            assert_eq!("a\u{fffd}b", value())
+        }
+
+        #[test]
+        fn replaces_invalid_characters_in_iter_items() {
+            assert_eq!(vec!["a\u{fffd}b", "c"], iter().collect::<Vec<_>>())
+        }
+
+        #[test]
+        fn replaces_invalid_characters_in_vec_items() {
+            assert_eq!(vec!["a\u{fffd}b", "c"], vec())
+        }
+    }
+}
+
+mod splits_records {
+    use super::*;
+
+    #[shell(separator = "\0")]
+    fn nul_separated() -> Vec<String> {
+        r#"
+        printf 'first\nfile\0second file\0'
+        "#
+    }
+
+    #[shell(separator = ",")]
+    fn comma_separated() -> impl Iterator<Item = u32> {
+        r#"
+        echo -n 1,2,3
+        "#
+    }
+
+    #[shell(separator = "paragraph")]
+    fn paragraphs() -> Result<impl Iterator<Item = String>, BoxedError> {
+        r#"
+        printf '\ncommit 1\nAuthor: a\n\n\ncommit 2\nAuthor: b\n  \ncommit 3\n'
+        "#
+    }
+
+    #[test]
+    fn splits_on_nul_byte() {
+        assert_eq!(vec!["first\nfile", "second file"], nul_separated())
+    }
+
+    #[test]
+    fn splits_on_custom_byte() {
+        assert_eq!(vec![1, 2, 3], comma_separated().collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn splits_on_blank_lines() {
+        assert_eq!(
+            vec!["commit 1\nAuthor: a", "commit 2\nAuthor: b", "commit 3"],
+            paragraphs().unwrap().collect::<Vec<_>>()
+        )
+    }
+}
+
+mod uses_custom_parser {
+    use super::*;
+    use std::num::ParseIntError;
+
+    fn parse_sum(output: &str) -> Result<u32, ParseIntError> {
+        output
+            .split_whitespace()
+            .map(str::parse::<u32>)
+            .sum::<Result<u32, _>>()
+    }
+
+    fn parse_doubled(line: &str) -> Result<u32, ParseIntError> {
+        line.parse::<u32>().map(|v| v * 2)
+    }
+
+    fn parse_words(output: &str) -> Result<Vec<String>, ParseIntError> {
+        Ok(output.split_whitespace().map(str::to_string).collect())
+    }
+
+    #[shell(parse_with = parse_sum)]
+    fn sum(data: &str) -> Result<u32, BoxedError> {
+        r#"
+        echo $DATA
+        "#
+    }
+
+    #[shell(parse_with = "self::parse_words")]
+    fn words() -> Vec<String> {
+        r#"
+        echo "foo bar"; echo baz
+        "#
+    }
+
+    #[shell(parse_lines_with = parse_doubled)]
+    fn doubled_iter() -> impl Iterator<Item = Result<u32, BoxedError>> {
+        r#"
+        echo 1; echo FOO; echo 3
+        "#
+    }
+
+    #[shell(parse_lines_with = parse_doubled)]
+    fn doubled_vec() -> Vec<u32> {
+        r#"
+        echo 1; echo 2; echo 3
+        "#
+    }
+
+    #[test]
+    fn parses_whole_output_with_parse_with() {
+        assert_eq!(6, sum("1 2 3").unwrap())
+    }
+
+    #[test]
+    fn returns_error_when_parse_with_fails() {
+        assert!(sum("1 FOO").is_err())
+    }
+
+    #[test]
+    fn parses_whole_output_into_vec_with_parse_with() {
+        assert_eq!(vec!["foo", "bar", "baz"], words())
+    }
+
+    #[test]
+    fn parses_iterator_items_with_parse_lines_with() {
+        let actual = doubled_iter().collect::<Vec<_>>();
+
+        assert_eq!(3, actual.len());
+        assert_eq!(2, *actual[0].as_ref().unwrap());
+        assert!(actual[1].is_err());
+        assert_eq!(6, *actual[2].as_ref().unwrap());
+    }
+
+    #[test]
+    fn parses_vec_items_with_parse_lines_with() {
+        assert_eq!(vec![2, 4, 6], doubled_vec())
+    }
+}
+
+mod wraps_parse_error {
+    use super::*;
+    use std::str::FromStr;
+
+    #[derive(Debug, PartialEq)]
+    struct Even(u32);
+
+    impl FromStr for Even {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s.parse::<u32>() {
+                Ok(v) if v % 2 == 0 => Ok(Even(v)),
+                _ => Err(format!("{} is not even", s)),
+            }
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Flag;
+
+    impl FromStr for Flag {
+        type Err = ();
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            if s == "on" {
+                Ok(Flag)
+            } else {
+                Err(())
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    struct CustomError;
+
+    impl From<shellfn::Error<String>> for CustomError {
+        fn from(_: shellfn::Error<String>) -> Self {
+            CustomError
+        }
+    }
+
+    #[shell(wrap_parse_error)]
+    fn value(data: &str) -> Result<Even, BoxedError> {
+        r#"
+        echo -n $DATA
+        "#
+    }
+
+    #[shell(wrap_parse_error)]
+    fn flags() -> Result<Vec<Result<Flag, BoxedError>>, BoxedError> {
+        r#"
+        echo on; echo off
+        "#
+    }
+
+    #[shell(wrap_parse_error, parse_lines_with = Even::from_str)]
+    fn evens() -> impl Iterator<Item = Result<Even, BoxedError>> {
+        r#"
+        echo 2; echo 3
+        "#
+    }
+
+    #[shell]
+    fn custom_error(data: &str) -> Result<Even, CustomError> {
+        r#"
+        echo -n $DATA
+        "#
+    }
+
+    #[test]
+    fn returns_parsed_value() {
+        assert_eq!(Even(42), value("42").unwrap())
+    }
+
+    #[test]
+    fn returns_wrapped_string_error() {
+        assert_eq!(
+            "\"43 is not even\"",
+            value("43").unwrap_err().source().unwrap().to_string()
+        )
+    }
+
+    #[test]
+    fn returns_wrapped_unit_item_error() {
+        let actual = flags().unwrap();
+
+        assert_eq!(Flag, *actual[0].as_ref().unwrap());
+        assert!(actual[1].is_err());
+    }
+
+    #[test]
+    fn wraps_custom_parser_error() {
+        let actual = evens().collect::<Vec<_>>();
+
+        assert_eq!(Even(2), *actual[0].as_ref().unwrap());
+        assert!(actual[1].is_err());
+    }
+
+    #[test]
+    fn does_not_require_std_error_without_wrapping() {
+        assert!(custom_error("43").is_err())
+    }
+}
+
+mod parses_columns {
+    use super::*;
+
+    #[shell]
+    fn processes() -> Vec<(String, u32)> {
+        r#"
+        echo 'bash   1'
+        echo 'cargo  42'
+        "#
+    }
+
+    #[shell(delimiter = "\t")]
+    fn tab_separated() -> Result<(String, String, u8), BoxedError> {
+        r#"
+        printf 'first name\tlast name\t7\n'
+        "#
+    }
+
+    #[shell]
+    fn rows() -> impl Iterator<Item = Result<(String, u32), BoxedError>> {
+        r#"
+        echo 'one 1'
+        echo 'two two'
+        echo 'three'
+        echo 'four 4 4'
+        "#
+    }
+
+    fn column_error(error: &BoxedError) -> &shellfn::ColumnError {
+        match error.downcast_ref::<shellfn::Error<shellfn::ColumnError>>() {
+            Some(shellfn::Error::ParsingError(e)) => e,
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+
+    #[test]
+    fn splits_lines_on_whitespace() {
+        assert_eq!(
+            vec![("bash".to_string(), 1), ("cargo".to_string(), 42)],
+            processes()
+        )
+    }
+
+    #[test]
+    fn splits_value_on_delimiter() {
+        assert_eq!(
+            ("first name".to_string(), "last name".to_string(), 7),
+            tab_separated().unwrap()
+        )
+    }
+
+    #[test]
+    fn names_column_in_errors() {
+        let actual = rows().collect::<Vec<_>>();
+
+        assert_eq!(("one".to_string(), 1), *actual[0].as_ref().unwrap());
+        assert!(matches!(
+            column_error(actual[1].as_ref().unwrap_err()),
+            shellfn::ColumnError::Invalid { column: 1, .. }
+        ));
+        assert!(matches!(
+            column_error(actual[2].as_ref().unwrap_err()),
+            shellfn::ColumnError::Missing { column: 1 }
+        ));
+        assert!(matches!(
+            column_error(actual[3].as_ref().unwrap_err()),
+            shellfn::ColumnError::Unexpected { column: 2 }
+        ));
+    }
+}
+
+mod derives_from_shell_line {
+    use super::*;
+    use shellfn::FromShellLine;
+
+    #[derive(Debug, PartialEq, FromShellLine)]
+    struct Process {
+        pid:     u32,
+        #[shell_line(column = 2)]
+        command: String,
+    }
+
+    #[derive(Debug, PartialEq, FromShellLine)]
+    #[shell_line(delimiter = ':')]
+    struct Passwd {
+        name: String,
+        #[shell_line(column = 2)]
+        uid:  u32,
+    }
+
+    #[derive(Debug, PartialEq, FromShellLine)]
+    #[shell_line(key_value)]
+    struct Mount {
+        #[shell_line(key = "TARGET")]
+        target: String,
+        #[shell_line(key = "FSTYPE")]
+        fstype: String,
+    }
+
+    #[cfg(feature = "regex")]
+    #[derive(Debug, PartialEq, FromShellLine)]
+    #[shell_line(regex = r"^(?P<hash>\w+) (?P<subject>.*)$")]
+    struct Commit {
+        hash:    String,
+        subject: String,
+    }
+
+    #[shell]
+    fn processes() -> Vec<Process> {
+        r#"
+        echo '1 root /sbin/init'
+        echo '42 user cargo test'
+        "#
+    }
+
+    #[shell]
+    fn passwd() -> Result<Vec<Passwd>, BoxedError> {
+        r#"
+        echo 'root:x:0:0:root:/root:/bin/bash'
+        "#
+    }
+
+    #[shell]
+    fn mounts() -> impl Iterator<Item = Result<Mount, BoxedError>> {
+        r#"
+        echo 'TARGET=/ SOURCE=/dev/sda1 FSTYPE=ext4'
+        echo 'TARGET=/boot'
+        "#
+    }
+
+    #[cfg(feature = "regex")]
+    #[shell]
+    fn commits() -> impl Iterator<Item = Result<Commit, BoxedError>> {
+        r#"
+        echo '1a2b3c Fix the build'
+        echo '-'
+        "#
+    }
+
+    #[test]
+    fn binds_fields_to_columns() {
+        assert_eq!(
+            vec![
+                Process {
+                    pid:     1,
+                    command: "/sbin/init".to_string(),
+                },
+                Process {
+                    pid:     42,
+                    command: "cargo".to_string(),
+                },
+            ],
+            processes()
+        )
+    }
+
+    #[test]
+    fn splits_columns_on_delimiter() {
+        assert_eq!(
+            vec![Passwd {
+                name: "root".to_string(),
+                uid:  0,
+            }],
+            passwd().unwrap()
+        )
+    }
+
+    #[test]
+    fn binds_fields_to_keys() {
+        let actual = mounts().collect::<Vec<_>>();
+
+        assert_eq!(
+            Mount {
+                target: "/".to_string(),
+                fstype: "ext4".to_string(),
+            },
+            *actual[0].as_ref().unwrap()
+        );
+        match actual[1]
+            .as_ref()
+            .unwrap_err()
+            .downcast_ref::<shellfn::Error<shellfn::LineError>>()
+        {
+            Some(shellfn::Error::ParsingError(shellfn::LineError::MissingField { field })) => {
+                assert_eq!("fstype", *field)
+            }
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+
+    #[test]
+    fn names_field_in_errors() {
+        let actual = "x 1".parse::<Process>().unwrap_err();
+
+        assert!(matches!(
+            actual,
+            shellfn::LineError::InvalidField { field: "pid", .. }
+        ));
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn binds_fields_to_capture_groups() {
+        let actual = commits().collect::<Vec<_>>();
+
+        assert_eq!(
+            Commit {
+                hash:    "1a2b3c".to_string(),
+                subject: "Fix the build".to_string(),
+            },
+            *actual[0].as_ref().unwrap()
+        );
+        assert!(matches!(
+            actual[1]
+                .as_ref()
+                .unwrap_err()
+                .downcast_ref::<shellfn::Error<shellfn::LineError>>(),
+            Some(shellfn::Error::ParsingError(shellfn::LineError::NoMatch))
+        ));
+    }
+}
+
+mod parses_maps {
+    use super::*;
+    use std::collections::{BTreeMap, HashMap};
+
+    #[shell]
+    fn settings() -> HashMap<String, String> {
+        r#"
+        echo 'user.name=John Doe'
+        echo 'core.editor=vim'
+        echo
+        echo 'url=https://example.com/?a=b'
+        "#
+    }
+
+    #[shell(key_value_separator = ":")]
+    fn meminfo() -> Result<BTreeMap<String, u64>, BoxedError> {
+        r#"
+        echo 'MemTotal:   16318412'
+        echo 'MemFree:     1042736'
+        "#
+    }
+
+    #[shell]
+    fn duplicates() -> BTreeMap<String, u32> {
+        r#"
+        echo 'a=1'
+        echo 'a=2'
+        "#
+    }
+
+    #[shell(duplicate_keys = "first")]
+    fn first_duplicates() -> BTreeMap<String, u32> {
+        r#"
+        echo 'a=1'
+        echo 'a=2'
+        "#
+    }
+
+    #[shell(duplicate_keys = "error")]
+    fn rejected_duplicates() -> Result<BTreeMap<String, u32>, BoxedError> {
+        r#"
+        echo 'a=1'
+        echo 'a=2'
+        "#
+    }
+
+    #[shell]
+    fn invalid_value() -> Result<BTreeMap<String, u32>, BoxedError> {
+        r#"
+        echo 'a=one'
+        "#
+    }
+
+    #[cfg(feature = "indexmap")]
+    #[shell]
+    fn ordered() -> indexmap::IndexMap<String, u32> {
+        r#"
+        echo 'b=2'
+        echo 'a=1'
+        "#
+    }
+
+    fn key_value_error(error: &BoxedError) -> &shellfn::KeyValueError {
+        match error.downcast_ref::<shellfn::Error<shellfn::KeyValueError>>() {
+            Some(shellfn::Error::ParsingError(e)) => e,
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+
+    #[test]
+    fn splits_lines_on_first_separator() {
+        let actual = settings();
+
+        assert_eq!(3, actual.len());
+        assert_eq!("John Doe", actual["user.name"]);
+        assert_eq!("https://example.com/?a=b", actual["url"]);
+    }
+
+    #[test]
+    fn splits_lines_on_custom_separator() {
+        assert_eq!(
+            vec![
+                ("MemFree".to_string(), 1042736),
+                ("MemTotal".to_string(), 16318412)
+            ],
+            meminfo().unwrap().into_iter().collect::<Vec<_>>()
+        )
+    }
+
+    #[test]
+    fn keeps_last_duplicate_by_default() {
+        assert_eq!(2, duplicates()["a"])
+    }
+
+    #[test]
+    fn keeps_first_duplicate() {
+        assert_eq!(1, first_duplicates()["a"])
+    }
+
+    #[test]
+    fn returns_error_for_duplicate() {
+        assert!(matches!(
+            key_value_error(&rejected_duplicates().unwrap_err()),
+            shellfn::KeyValueError::DuplicateKey { key } if key == "a"
+        ))
+    }
+
+    #[test]
+    fn returns_error_for_invalid_value() {
+        assert!(matches!(
+            key_value_error(&invalid_value().unwrap_err()),
+            shellfn::KeyValueError::InvalidValue { key, .. } if key == "a"
+        ))
+    }
+
+    #[cfg(feature = "indexmap")]
+    #[test]
+    fn keeps_order_of_index_map() {
+        assert_eq!(vec!["b", "a"], ordered().keys().collect::<Vec<_>>())
+    }
+}
+
+mod collects_items {
+    use super::*;
+    use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+    use std::iter::FromIterator;
+
+    #[derive(Debug, PartialEq)]
+    struct Sum(u32);
+
+    impl FromIterator<u32> for Sum {
+        fn from_iter<I: IntoIterator<Item = u32>>(iter: I) -> Self {
+            Sum(iter.into_iter().sum())
+        }
+    }
+
+    #[shell]
+    fn unique() -> HashSet<String> {
+        "echo a; echo b; echo a"
+    }
+
+    #[shell]
+    fn sorted() -> Result<BTreeSet<u32>, BoxedError> {
+        "echo 3; echo 1; echo 2"
+    }
+
+    #[shell]
+    fn queue() -> VecDeque<Result<u32, BoxedError>> {
+        "echo 1; echo x"
+    }
+
+    #[shell(collect)]
+    fn sum() -> Sum {
+        "echo 1; echo 2; echo 3"
+    }
+
+    #[shell(collect)]
+    fn pairs() -> HashMap<String, u32> {
+        "echo 'a=1'; echo 'b=2'"
+    }
+
+    #[test]
+    fn collects_into_set() {
+        assert_eq!(
+            vec!["a".to_string(), "b".to_string()]
+                .into_iter()
+                .collect::<HashSet<_>>(),
+            unique()
+        )
+    }
+
+    #[test]
+    fn collects_into_set_wrapped_with_result() {
+        assert_eq!(
+            vec![1, 2, 3],
+            sorted().unwrap().into_iter().collect::<Vec<_>>()
+        )
+    }
+
+    #[test]
+    fn collects_item_errors() {
+        let actual = queue();
+
+        assert_eq!(2, actual.len());
+        assert_eq!(1, *actual[0].as_ref().unwrap());
+        assert!(actual[1].is_err());
+    }
+
+    #[test]
+    fn collects_into_any_from_iterator_with_collect_flag() {
+        assert_eq!(Sum(6), sum())
+    }
+
+    #[test]
+    fn parses_map_from_key_value_lines_with_collect_flag() {
+        let actual = pairs();
+
+        assert_eq!(1, actual["a"]);
+        assert_eq!(2, actual["b"]);
+    }
+}
+
+mod overrides_return_shape {
+    use super::*;
+
+    type Res<T> = Result<T, BoxedError>;
+
+    #[shell(returns = "value", fallible)]
+    fn value() -> Res<u32> {
+        "echo -n 42"
+    }
+
+    #[shell(returns = "unit", fallible)]
+    fn unit() -> Res<()> {
+        "exit 1"
+    }
+
+    #[shell(returns = "vec", fallible)]
+    fn vec() -> Res<Vec<u32>> {
+        "echo 1; echo 2"
+    }
+
+    #[shell(returns = "iter", fallible)]
+    fn iter() -> Res<impl Iterator<Item = u32>> {
+        "echo 1; echo 2"
+    }
+
+    #[shell]
+    fn boxed() -> Box<dyn Iterator<Item = u32>> {
+        "echo 1; echo 2"
+    }
+
+    #[shell]
+    fn boxed_result() -> Result<Box<dyn Iterator<Item = Result<u32, BoxedError>>>, BoxedError> {
+        "echo 1; echo x"
+    }
+
+    #[shell]
+    fn into_iter() -> impl IntoIterator<Item = u32> {
+        "echo 1; echo 2"
+    }
+
+    #[test]
+    fn returns_value_for_alias() {
+        assert_eq!(42, value().unwrap())
+    }
+
+    #[test]
+    fn returns_unit_for_alias() {
+        assert!(unit().is_err())
+    }
+
+    #[test]
+    fn returns_vec_for_alias() {
+        assert_eq!(vec![1, 2], vec().unwrap())
+    }
+
+    #[test]
+    fn returns_iterator_for_alias() {
+        assert_eq!(vec![1, 2], iter().unwrap().collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn returns_boxed_iterator() {
+        assert_eq!(vec![1, 2], boxed().collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn returns_boxed_iterator_wrapped_with_result() {
+        let actual = boxed_result().unwrap().collect::<Vec<_>>();
+
+        assert_eq!(1, *actual[0].as_ref().unwrap());
+        assert!(actual[1].is_err());
+    }
+
+    #[test]
+    fn returns_into_iterator() {
+        assert_eq!(vec![1, 2], into_iter().into_iter().collect::<Vec<_>>())
+    }
+}
+
+mod returns_option {
+    use super::*;
+
+    #[shell]
+    fn value() -> Option<u32> {
+        "echo -n 42"
+    }
+
+    #[shell]
+    fn empty() -> Option<u32> {
+        "echo '  '"
+    }
+
+    #[shell(none_codes = [1])]
+    fn not_found() -> Result<Option<String>, BoxedError> {
+        "echo missing | grep -x present"
+    }
+
+    #[shell(none_codes = [1])]
+    fn failed() -> Result<Option<String>, BoxedError> {
+        "exit 2"
+    }
+
+    #[shell]
+    fn invalid() -> Result<Option<u32>, BoxedError> {
+        "echo -n x"
+    }
+
+    #[shell]
+    fn pair() -> Option<(String, u32)> {
+        "echo 'a 1'"
+    }
+
+    #[test]
+    fn returns_some_for_output() {
+        assert_eq!(Some(42), value())
+    }
+
+    #[test]
+    fn returns_none_for_blank_output() {
+        assert_eq!(None, empty())
+    }
+
+    #[test]
+    fn returns_none_for_none_code() {
+        assert_eq!(None, not_found().unwrap())
+    }
+
+    #[test]
+    fn returns_error_for_other_exit_code() {
+        assert!(failed().is_err())
+    }
+
+    #[test]
+    fn returns_error_when_parsing_fails() {
+        assert!(invalid().is_err())
+    }
+
+    #[test]
+    fn parses_tuple() {
+        assert_eq!(Some(("a".to_string(), 1)), pair())
+    }
+}
+
+mod normalizes_output {
+    use super::*;
+
+    #[shell(trim)]
+    fn trimmed() -> u32 {
+        "echo ' 42 '"
+    }
+
+    #[shell(trim)]
+    fn trimmed_items() -> Vec<String> {
+        "echo ' a '; echo 'b  '"
+    }
+
+    #[shell(skip_empty)]
+    fn without_empty() -> Vec<u32> {
+        "echo 1; echo; echo '  '; echo 2"
+    }
+
+    #[shell(strip_ansi)]
+    fn colored() -> impl Iterator<Item = String> {
+        r#"printf '\033[1;31mred\033[0m\n\033]8;;http://example.com\033\\link\033]8;;\033\\\n'"#
+    }
+
+    #[shell(skip_comments = "#", trim)]
+    fn configured() -> u32 {
+        r#"
+        echo '# the answer'
+        echo 42
+        "#
+    }
+
+    #[shell(skip_comments = "//")]
+    fn commented() -> Vec<u32> {
+        "echo '// one'; echo 1; echo '  // two'; echo 2"
+    }
+
+    #[shell(skip_comments = "#")]
+    fn only_comments() -> Option<u32> {
+        "echo '# nothing'"
+    }
+
+    #[test]
+    fn trims_value() {
+        assert_eq!(42, trimmed())
+    }
+
+    #[test]
+    fn trims_items() {
+        assert_eq!(vec!["a", "b"], trimmed_items())
+    }
+
+    #[test]
+    fn skips_empty_lines() {
+        assert_eq!(vec![1, 2], without_empty())
+    }
+
+    #[test]
+    fn strips_ansi_escape_sequences() {
+        assert_eq!(vec!["red", "link"], colored().collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn skips_comments_in_value() {
+        assert_eq!(42, configured())
+    }
+
+    #[test]
+    fn skips_comment_items() {
+        assert_eq!(vec![1, 2], commented())
+    }
+
+    #[test]
+    fn returns_none_when_every_line_is_skipped() {
+        assert_eq!(None, only_comments())
+    }
+}
+
+mod parses_from_stream {
+    use super::*;
+    use std::num::ParseIntError;
+
+    #[shell(parse_from = "stderr")]
+    fn from_stderr() -> u32 {
+        "echo -n 42 >&2"
+    }
+
+    #[shell(parse_from = "stderr")]
+    fn items_from_stderr() -> Vec<u32> {
+        "echo 1 >&2; echo 2 >&2"
+    }
+
+    #[shell(parse_from = "stderr")]
+    fn failing_from_stderr() -> Result<u32, shellfn::Error<ParseIntError>> {
+        "echo -n 42 >&2; exit 1"
+    }
+
+    #[shell(parse_from = "both")]
+    fn from_both() -> Vec<u32> {
+        "echo 1; echo 2 >&2; echo 3"
+    }
+
+    #[shell(parse_from = "both")]
+    fn lazily_from_both() -> impl Iterator<Item = String> {
+        "echo out; echo err >&2"
+    }
+
+    #[test]
+    fn parses_value_from_stderr() {
+        assert_eq!(42, from_stderr())
+    }
+
+    #[test]
+    fn parses_items_from_stderr() {
+        assert_eq!(vec![1, 2], items_from_stderr())
+    }
+
+    #[test]
+    fn keeps_stderr_of_failed_process() {
+        match failing_from_stderr() {
+            Err(shellfn::Error::ProcessFailed(output)) => assert_eq!(b"42", &output.stderr[..]),
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn parses_items_from_both_streams() {
+        let mut items = from_both();
+        items.sort();
+        assert_eq!(vec![1, 2, 3], items)
+    }
+
+    #[test]
+    fn iterates_over_both_streams() {
+        let mut items = lazily_from_both().collect::<Vec<_>>();
+        items.sort();
+        assert_eq!(vec!["err", "out"], items)
+    }
+}
+
+mod parses_result_fd {
+    use super::*;
+
+    #[shell(result_fd = 3)]
+    fn value() -> u32 {
+        "echo computing...; echo -n 42 >&3"
+    }
+
+    #[shell(result_fd = 3)]
+    fn items() -> Vec<u32> {
+        "echo 1 >&3; echo computing...; echo 2 >&3"
+    }
+
+    #[shell(result_fd = 4)]
+    fn other_fd() -> impl Iterator<Item = String> {
+        "echo a >&4; echo b >&4"
+    }
+
+    #[shell(result_fd = 3)]
+    fn failing() -> Result<u32, Box<dyn StdError>> {
+        "echo -n 42 >&3; exit 1"
+    }
+
+    #[shell(result_fd = 3)]
+    fn nothing_written() -> Option<u32> {
+        "echo 42"
+    }
+
+    #[test]
+    fn parses_value_written_to_fd() {
+        assert_eq!(42, value())
+    }
+
+    #[test]
+    fn parses_items_written_to_fd() {
+        assert_eq!(vec![1, 2], items())
+    }
+
+    #[test]
+    fn parses_any_fd() {
+        assert_eq!(vec!["a", "b"], other_fd().collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn returns_error_when_script_fails() {
+        assert!(failing().is_err())
+    }
+
+    #[test]
+    fn ignores_stdout() {
+        assert_eq!(None, nothing_written())
+    }
+}
+
+#[cfg(feature = "log")]
+mod bridges_log {
+    use super::*;
+    use log::{Level, LevelFilter, Log, Metadata, Record};
+    use std::sync::{Mutex, Once};
+
+    static RECORDS: Mutex<Vec<(String, Level, String)>> = Mutex::new(Vec::new());
+
+    struct TestLogger;
+
+    impl Log for TestLogger {
+        fn enabled(&self, _: &Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record) {
+            RECORDS.lock().unwrap().push((
+                record.target().to_string(),
+                record.level(),
+                record.args().to_string(),
+            ));
+        }
+
+        fn flush(&self) {}
+    }
+
+    fn records_of(function: &str) -> Vec<(Level, String)> {
+        let target = format!("{}::{}", module_path!(), function);
+
+        RECORDS
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(record_target, _, _)| *record_target == target)
+            .map(|(_, level, message)| (*level, message.clone()))
+            .collect()
+    }
+
+    fn init_logger() {
+        static INIT: Once = Once::new();
+
+        INIT.call_once(|| {
+            log::set_logger(&TestLogger).unwrap();
+            log::set_max_level(LevelFilter::Trace);
+        });
+    }
+
+    #[shell(log_bridge)]
+    fn cleanup() {
+        r#"
+        shellfn_log warn "disk almost full"
+        shellfn_log INFO done
+        "#
+    }
+
+    #[shell(log_bridge)]
+    fn count() -> u32 {
+        "shellfn_log debug counting; echo -n 3"
+    }
+
+    #[shell(log_bridge)]
+    fn items() -> impl Iterator<Item = u32> {
+        "echo 1; shellfn_log error oops; echo 2"
+    }
+
+    #[shell(log_bridge)]
+    fn without_level() {
+        "shellfn_log something happened"
+    }
+
+    #[shell(log_bridge)]
+    fn authenticate(#[secret] token: &str) {
+        r#"shellfn_log info "using $(printenv TOKEN)""#
+    }
+
+    #[test]
+    fn forwards_records_with_levels() {
+        init_logger();
+        cleanup();
+
+        assert_eq!(
+            vec![
+                (Level::Warn, "disk almost full".to_string()),
+                (Level::Info, "done".to_string())
+            ],
+            records_of("cleanup")
+        )
+    }
+
+    #[test]
+    fn forwards_records_of_value() {
+        init_logger();
+
+        assert_eq!(3, count());
+        assert_eq!(
+            vec![(Level::Debug, "counting".to_string())],
+            records_of("count")
+        )
+    }
+
+    #[test]
+    fn forwards_records_of_iterator() {
+        init_logger();
+
+        assert_eq!(vec![1, 2], items().collect::<Vec<_>>());
+        assert_eq!(
+            vec![(Level::Error, "oops".to_string())],
+            records_of("items")
+        )
+    }
+
+    #[test]
+    fn logs_lines_without_level_as_info() {
+        init_logger();
+        without_level();
+
+        assert_eq!(
+            vec![(Level::Info, "something happened".to_string())],
+            records_of("without_level")
+        )
+    }
+
+    #[test]
+    fn redacts_secrets() {
+        init_logger();
+        authenticate("s3cr3t");
+
+        assert_eq!(
+            vec![(Level::Info, "using ***".to_string())],
+            records_of("authenticate")
+        )
+    }
+}
+
+#[cfg(feature = "tracing")]
+mod traces_calls {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::fmt::Debug;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Level, Metadata, Subscriber};
+
+    type Fields = BTreeMap<String, String>;
+
+    #[derive(Default)]
+    struct Recorded {
+        spans:  Vec<(&'static str, Fields)>,
+        events: Vec<(Option<&'static str>, Level, Fields)>,
+    }
+
+    #[derive(Default)]
+    struct TestSubscriber {
+        recorded: Arc<Mutex<Recorded>>,
+    }
+
+    struct FieldVisitor<'a>(&'a mut Fields);
+
+    impl Visit for FieldVisitor<'_> {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name().to_string(), value.to_string());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.0
+                .insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+
+    impl Subscriber for TestSubscriber {
+        fn enabled(&self, _: &Metadata) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes) -> Id {
+            let mut fields = Fields::new();
+            span.record(&mut FieldVisitor(&mut fields));
+
+            let mut recorded = self.recorded.lock().unwrap();
+            recorded.spans.push((span.metadata().name(), fields));
+
+            Id::from_u64(recorded.spans.len() as u64)
+        }
+
+        fn record(&self, span: &Id, values: &Record) {
+            let mut recorded = self.recorded.lock().unwrap();
+            let (_, fields) = &mut recorded.spans[span.into_u64() as usize - 1];
+            values.record(&mut FieldVisitor(fields));
+        }
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, event: &Event) {
+            let mut fields = Fields::new();
+            event.record(&mut FieldVisitor(&mut fields));
+
+            let mut recorded = self.recorded.lock().unwrap();
+            let parent = event
+                .parent()
+                .map(|id| recorded.spans[id.into_u64() as usize - 1].0);
+            recorded
+                .events
+                .push((parent, *event.metadata().level(), fields));
+        }
+
+        fn enter(&self, _: &Id) {}
+
+        fn exit(&self, _: &Id) {}
+    }
+
+    fn traced(call: impl FnOnce()) -> Recorded {
+        let subscriber = TestSubscriber::default();
+        let recorded = subscriber.recorded.clone();
+
+        tracing::subscriber::with_default(subscriber, call);
+
+        Arc::try_unwrap(recorded)
+            .unwrap_or_else(|_| panic!("subscriber still in use"))
+            .into_inner()
+            .unwrap()
+    }
+
+    #[shell]
+    fn greet(name: &str, greeting: &str) -> String {
+        r#"echo "$GREETING"; echo "$NAME""#
+    }
+
+    #[shell]
+    fn numbers() -> impl Iterator<Item = u32> {
+        "echo 1; echo 2; printf 3"
+    }
+
+    #[shell]
+    fn fail() -> Result<(), Box<dyn StdError>> {
+        "exit 3"
+    }
+
+    #[shell]
+    fn fail_items() -> Result<impl Iterator<Item = u32>, Box<dyn StdError>> {
+        "echo 1; exit 3"
+    }
+
+    #[shell(cmd = "definitely-not-a-command")]
+    fn missing() -> Result<(), Box<dyn StdError>> {
+        ""
+    }
+
+    #[test]
+    fn records_span_of_call() {
+        let recorded = traced(|| {
+            greet("world", "hello");
+        });
+        let (name, fields) = &recorded.spans[0];
+
+        assert_eq!(1, recorded.spans.len());
+        assert_eq!("greet", *name);
+        assert_eq!("bash", fields["interpreter"]);
+        assert_eq!("NAME,GREETING", fields["envs"]);
+        assert_eq!("0", fields["exit_code"]);
+        assert_eq!("2", fields["lines"]);
+        assert!(fields.contains_key("pid"));
+        assert!(fields.contains_key("duration_ms"));
+        assert!(recorded.events.is_empty());
+    }
+
+    #[test]
+    fn counts_lines_of_iterator() {
+        let recorded = traced(|| assert_eq!(vec![1, 2, 3], numbers().collect::<Vec<_>>()));
+
+        assert_eq!("3", recorded.spans[0].1["lines"]);
+    }
+
+    #[test]
+    fn records_error_exit_as_event() {
+        let recorded = traced(|| assert!(fail().is_err()));
+        let (parent, level, fields) = &recorded.events[0];
+
+        assert_eq!("3", recorded.spans[0].1["exit_code"]);
+        assert_eq!(Some("fail"), *parent);
+        assert_eq!(Level::WARN, *level);
+        assert_eq!("3", fields["exit_code"]);
+    }
+
+    #[test]
+    fn records_error_exit_of_iterator_as_event() {
+        let recorded = traced(|| assert_eq!(1, fail_items().unwrap().count()));
+        let (parent, level, fields) = &recorded.events[0];
+
+        assert_eq!("3", recorded.spans[0].1["exit_code"]);
+        assert_eq!("1", recorded.spans[0].1["lines"]);
+        assert_eq!(Some("fail_items"), *parent);
+        assert_eq!(Level::WARN, *level);
+        assert_eq!("3", fields["exit_code"]);
+    }
+
+    #[test]
+    fn records_spawn_failure_as_event() {
+        let recorded = traced(|| assert!(missing().is_err()));
+        let (parent, level, _) = &recorded.events[0];
+
+        assert_eq!(Some("missing"), *parent);
+        assert_eq!(Level::ERROR, *level);
+        assert!(!recorded.spans[0].1.contains_key("pid"));
+    }
+}
+
+#[cfg(feature = "metrics")]
+mod records_metrics {
+    use super::*;
+    use metrics::{
+        Counter, CounterFn, Gauge, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder,
+        SharedString, Unit,
+    };
+    use std::sync::{Arc, Mutex};
+
+    type Recorded = Arc<Mutex<Vec<(String, String)>>>;
+
+    #[derive(Default)]
+    struct TestRecorder {
+        recorded: Recorded,
+    }
+
+    // every increment and histogram value is recorded as `(metric, function)`
+    struct Handle {
+        metric:   (String, String),
+        recorded: Recorded,
+    }
+
+    impl Handle {
+        fn record(&self) {
+            self.recorded.lock().unwrap().push(self.metric.clone());
+        }
+    }
+
+    impl CounterFn for Handle {
+        fn increment(&self, _: u64) {
+            self.record()
+        }
+
+        fn absolute(&self, _: u64) {}
+    }
+
+    impl HistogramFn for Handle {
+        fn record(&self, _: f64) {
+            Handle::record(self)
+        }
+    }
+
+    impl TestRecorder {
+        fn handle(&self, key: &Key) -> Arc<Handle> {
+            let function = key
+                .labels()
+                .find(|label| label.key() == "function")
+                .map(|label| label.value().to_string())
+                .unwrap_or_default();
+
+            Arc::new(Handle {
+                metric:   (key.name().to_string(), function),
+                recorded: self.recorded.clone(),
+            })
+        }
+    }
+
+    impl Recorder for TestRecorder {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn register_counter(&self, key: &Key, _: &Metadata) -> Counter {
+            Counter::from_arc(self.handle(key))
+        }
+
+        fn register_gauge(&self, _: &Key, _: &Metadata) -> Gauge {
+            Gauge::noop()
+        }
+
+        fn register_histogram(&self, key: &Key, _: &Metadata) -> Histogram {
+            Histogram::from_arc(self.handle(key))
+        }
+    }
+
+    fn metrics_of(function: &str, call: impl FnOnce()) -> Vec<String> {
+        let recorder = TestRecorder::default();
+        let function = format!("{}::{}", module_path!(), function);
+
+        metrics::with_local_recorder(&recorder, call);
+
+        let recorded = recorder.recorded.lock().unwrap();
+
+        recorded
+            .iter()
+            .filter(|(_, label)| *label == function)
+            .map(|(metric, _)| metric.clone())
+            .collect()
+    }
+
+    #[shell]
+    fn count() -> u32 {
+        "echo -n 3"
+    }
+
+    #[shell]
+    fn fail() -> Result<(), Box<dyn StdError>> {
+        "exit 1"
+    }
+
+    #[shell]
+    fn numbers() -> impl Iterator<Item = Result<u32, Box<dyn StdError>>> {
+        "echo 1; echo two; echo three"
+    }
+
+    #[shell]
+    fn fail_items() -> Result<impl Iterator<Item = u32>, Box<dyn StdError>> {
+        "echo 1; exit 1"
+    }
+
+    #[shell(cmd = "definitely-not-a-command")]
+    fn missing() -> Result<(), Box<dyn StdError>> {
+        ""
+    }
+
+    #[test]
+    fn records_invocation_and_duration() {
+        assert_eq!(
+            vec!["shellfn_invocations_total", "shellfn_duration_seconds"],
+            metrics_of("count", || assert_eq!(3, count()))
+        )
+    }
+
+    #[test]
+    fn records_failed_exit() {
+        assert_eq!(
+            vec![
+                "shellfn_invocations_total",
+                "shellfn_failed_exits_total",
+                "shellfn_duration_seconds"
+            ],
+            metrics_of("fail", || assert!(fail().is_err()))
+        )
+    }
+
+    #[test]
+    fn records_failed_exit_of_iterator() {
+        assert_eq!(
+            vec![
+                "shellfn_invocations_total",
+                "shellfn_failed_exits_total",
+                "shellfn_duration_seconds"
+            ],
+            metrics_of("fail_items", || assert_eq!(
+                1,
+                fail_items().unwrap().count()
+            ))
+        )
+    }
+
+    #[test]
+    fn records_parse_failures() {
+        assert_eq!(
+            vec![
+                "shellfn_invocations_total",
+                "shellfn_parse_failures_total",
+                "shellfn_parse_failures_total",
+                "shellfn_duration_seconds"
+            ],
+            metrics_of("numbers", || assert_eq!(3, numbers().count()))
+        )
+    }
+
+    #[test]
+    fn records_spawn_failure() {
+        assert_eq!(
+            vec![
+                "shellfn_invocations_total",
+                "shellfn_spawn_failures_total",
+                "shellfn_duration_seconds"
+            ],
+            metrics_of("missing", || assert!(missing().is_err()))
+        )
+    }
+}
+
+mod runs_hooks {
+    use super::*;
+    use shellfn::{Invocation, Outcome, Veto};
+    use std::io::ErrorKind;
+    use std::sync::{Mutex, Once};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    static EXITS: Mutex<Vec<(String, Option<i32>)>> = Mutex::new(Vec::new());
+
+    // hooks are global, so they only touch the functions of this module
+    fn is_own(invocation: &Invocation, function: &str) -> bool {
+        invocation.name() == Some(&format!("{}::{}", module_path!(), function))
+    }
+
+    fn register_hooks() {
+        static INIT: Once = Once::new();
+
+        INIT.call_once(|| {
+            shellfn::before_spawn(|invocation| {
+                if is_own(invocation, "correlated") {
+                    invocation.set_env("CORRELATION_ID", "42");
+                }
+
+                Ok(())
+            });
+            shellfn::before_spawn(|invocation| {
+                if is_own(invocation, "forbidden") {
+                    return Err(Veto::new("read-only mode"));
+                }
+
+                Ok(())
+            });
+            shellfn::after_exit(|invocation, outcome| {
+                let observed = [
+                    "observed",
+                    "observed_items",
+                    "observed_unchecked",
+                    "observed_slow",
+                    "forbidden",
+                ];
+
+                if observed.iter().any(|function| is_own(invocation, function)) {
+                    let code = match outcome {
+                        Outcome::Exited(status) => status.code(),
+                        Outcome::SpawnFailed(_) => None,
+                    };
+
+                    EXITS
+                        .lock()
+                        .unwrap()
+                        .push((invocation.name().unwrap().to_string(), code));
+                }
+            });
+            shellfn::after_exit(|invocation, _| {
+                if is_own(invocation, "reentrant") {
+                    shellfn::after_exit(|_, _| {});
+
+                    let nested = correlated().trim().parse().ok();
+                    EXITS
+                        .lock()
+                        .unwrap()
+                        .push((invocation.name().unwrap().to_string(), nested));
+                }
+            });
+        });
+    }
+
+    fn exits_of(function: &str) -> Vec<Option<i32>> {
+        let name = format!("{}::{}", module_path!(), function);
+
+        EXITS
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(exit_name, _)| *exit_name == name)
+            .map(|(_, code)| *code)
+            .collect()
+    }
+
+    // the exit of a process dropped while still running is reported by a background thread
+    fn eventual_exits_of(function: &str, count: usize) -> Vec<Option<i32>> {
+        let started = Instant::now();
+
+        while exits_of(function).len() < count && started.elapsed() < Duration::from_secs(10) {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        exits_of(function)
+    }
+
+    #[shell]
+    fn correlated() -> String {
+        "printenv CORRELATION_ID"
+    }
+
+    #[shell]
+    fn forbidden() -> Result<(), Box<dyn StdError>> {
+        "exit 0"
+    }
+
+    #[shell]
+    fn observed(code: u8) -> Result<(), Box<dyn StdError>> {
+        "exit $CODE"
+    }
+
+    #[shell]
+    fn observed_items() -> Result<impl Iterator<Item = u32>, Box<dyn StdError>> {
+        "echo 1; exit 4"
+    }
+
+    #[shell]
+    fn observed_slow() -> impl Iterator<Item = String> {
+        "echo first; sleep 1"
+    }
+
+    #[shell(no_panic)]
+    fn observed_unchecked() -> impl Iterator<Item = u32> {
+        "echo 1; exit 5"
+    }
+
+    #[shell]
+    fn reentrant() {
+        "true"
+    }
+
+    #[test]
+    fn modifies_invocation_before_spawn() {
+        register_hooks();
+
+        assert_eq!("42\n", correlated());
+    }
+
+    #[test]
+    fn vetoes_invocation() {
+        register_hooks();
+
+        let error = forbidden().unwrap_err();
+        let error = error
+            .downcast_ref::<shellfn::Error<shellfn::NeverError>>()
+            .unwrap();
+
+        match error {
+            shellfn::Error::ProcessNotSpawned(e) => {
+                assert_eq!(ErrorKind::PermissionDenied, e.kind());
+                assert_eq!("invocation vetoed: read-only mode", e.to_string());
+            }
+            e => panic!("unexpected error {:?}", e),
+        }
+        assert!(exits_of("forbidden").contains(&None));
+    }
+
+    #[test]
+    fn runs_after_exit() {
+        register_hooks();
+
+        assert!(observed(3).is_err());
+        assert!(observed(0).is_ok());
+        assert_eq!(vec![Some(3), Some(0)], exits_of("observed"));
+    }
+
+    #[test]
+    fn runs_hooks_registering_hooks_and_calling_functions() {
+        register_hooks();
+        reentrant();
+
+        assert_eq!(vec![Some(42)], exits_of("reentrant"));
+    }
+
+    #[test]
+    fn runs_after_exit_of_iterator() {
+        register_hooks();
+
+        assert_eq!(vec![1], observed_items().unwrap().collect::<Vec<_>>());
+        assert_eq!(Some(1), observed_items().unwrap().next());
+        assert_eq!(
+            vec![Some(4), Some(4)],
+            eventual_exits_of("observed_items", 2)
+        );
+    }
+
+    #[test]
+    fn does_not_block_on_dropped_iterator() {
+        register_hooks();
+
+        let mut items = observed_slow();
+        assert_eq!(Some("first".to_string()), items.next());
+
+        let started = Instant::now();
+        drop(items);
+
+        assert!(started.elapsed() < Duration::from_millis(500));
+        assert_eq!(vec![Some(0)], eventual_exits_of("observed_slow", 1));
+    }
+
+    #[test]
+    fn runs_after_exit_of_no_panic_iterator() {
+        register_hooks();
+
+        assert_eq!(vec![1], observed_unchecked().collect::<Vec<_>>());
+        assert_eq!(vec![Some(5)], exits_of("observed_unchecked"));
+    }
+}
+
+#[cfg(feature = "audit")]
+mod audits_invocations {
+    use super::*;
+    use serde_json::Value;
+    use std::path::PathBuf;
+    use std::sync::Once;
+
+    // the audit log is global, so every test reads only the records of its own function
+    fn audit_log() -> PathBuf {
+        static INIT: Once = Once::new();
+        let path = std::env::temp_dir().join(format!("shellfn-audit-{}.jsonl", std::process::id()));
+
+        INIT.call_once(|| {
+            let _ = std::fs::remove_file(&path);
+            shellfn::set_audit_log(Some(&path));
+        });
+
+        path
+    }
+
+    fn records_of(function: &str) -> Vec<Value> {
+        let name = format!("{}::{}", module_path!(), function);
+
+        std::fs::read_to_string(audit_log())
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .filter(|record| record["function"] == name.as_str())
+            .collect()
+    }
+
+    #[shell]
+    fn deploy(target: &str) -> Result<(), Box<dyn StdError>> {
+        "exit 2"
+    }
+
+    #[shell]
+    fn list_targets() -> Result<impl Iterator<Item = String>, Box<dyn StdError>> {
+        "echo staging; echo production; exit 3"
+    }
+
+    #[shell(cmd = "definitely-not-a-command")]
+    fn missing() -> Result<(), Box<dyn StdError>> {
+        ""
+    }
+
+    #[shell(cmd = "sh -c PROGRAM sh $TOKEN")]
+    fn authenticate(#[secret] token: &str) {
+        "true"
+    }
+
+    #[test]
+    fn appends_record_of_invocation() {
+        audit_log();
+        assert!(deploy("production").is_err());
+
+        let records = records_of("deploy");
+        let record = &records[0];
+        let argv = record["argv"].as_array().unwrap();
+
+        assert_eq!(1, records.len());
+        assert_eq!("bash", record["interpreter"]);
+        assert_eq!("bash", argv[0]);
+        assert_eq!("-c", argv[1]);
+        assert!(argv[2].as_str().unwrap().starts_with("sha256:"));
+        assert_eq!(71, argv[2].as_str().unwrap().len());
+        assert_eq!(vec!["TARGET"], record["envs"].as_array().unwrap().clone());
+        assert_eq!(2, record["exit_code"]);
+        assert!(record["error"].is_null());
+        assert!(record["duration_ms"].is_u64());
+        assert!(record["cwd"].is_string());
+        assert!(record["timestamp"].as_str().unwrap().ends_with('Z'));
+    }
+
+    #[test]
+    fn appends_record_of_iterator() {
+        audit_log();
+        assert_eq!(2, list_targets().unwrap().count());
+
+        let records = records_of("list_targets");
+
+        assert_eq!(1, records.len());
+        assert_eq!(3, records[0]["exit_code"]);
+    }
+
+    #[test]
+    fn records_spawn_failure() {
+        audit_log();
+        assert!(missing().is_err());
+
+        let records = records_of("missing");
+
+        assert_eq!(1, records.len());
+        assert!(records[0]["exit_code"].is_null());
+        assert!(records[0]["error"].is_string());
+    }
+
+    #[test]
+    fn redacts_secrets() {
+        audit_log();
+        authenticate("s3cr3t");
+
+        let records = records_of("authenticate");
+
+        assert_eq!("***", records[0]["argv"][4]);
+        assert_eq!(
+            vec!["TOKEN"],
+            records[0]["envs"].as_array().unwrap().clone()
+        );
+    }
+}
+
+mod redacts_secrets {
+    use super::*;
+    use std::sync::{Mutex, Once};
+
+    static INVOCATIONS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    fn record_invocations() {
+        static INIT: Once = Once::new();
+
+        INIT.call_once(|| {
+            shellfn::before_spawn(|invocation| {
+                if invocation.name() == Some(&format!("{}::with_arg", module_path!())) {
+                    INVOCATIONS
+                        .lock()
+                        .unwrap()
+                        .push(format!("{:?}", invocation));
+                }
+
+                Ok(())
+            });
+        });
+    }
+
+    #[shell]
+    fn exported(#[secret] token: &str) -> String {
+        "printenv TOKEN"
+    }
+
+    #[shell(cmd = "sh -c PROGRAM sh $TOKEN")]
+    fn with_arg(#[secret] token: &str, user: &str) -> String {
+        r#"echo "$1""#
+    }
+
+    #[shell]
+    fn login(#[secret] token: &str) -> Result<String, Box<dyn StdError>> {
+        "echo \"invalid token $(printenv TOKEN)\"; exit 1"
+    }
+
+    #[shell]
+    fn undecodable(#[secret] token: &str) -> Result<String, Box<dyn StdError>> {
+        r#"printf '%s \377' "$TOKEN""#
+    }
+
+    #[test]
+    fn exports_value_to_child() {
+        assert_eq!("s3cr3t\n", exported("s3cr3t"));
+    }
+
+    #[test]
+    fn redacts_debug_of_invocation() {
+        record_invocations();
+
+        assert_eq!("s3cr3t\n", with_arg("s3cr3t", "alice"));
+
+        let invocation = INVOCATIONS.lock().unwrap()[0].clone();
+
+        assert!(!invocation.contains("s3cr3t"));
+        assert!(invocation.contains(r#"["-c", "echo \"$1\"", "sh", "***"]"#));
+        assert!(invocation.contains(r#"("TOKEN", "***"), ("USER", "alice")"#));
+    }
+
+    #[test]
+    fn redacts_output_of_failed_process() {
+        let error = format!("{:?}", login("s3cr3t").unwrap_err());
+
+        assert!(!error.contains("s3cr3t"));
+        assert!(error.contains("***"));
+    }
+
+    #[test]
+    fn redacts_undecodable_output() {
+        let error = undecodable("s3cr3t").unwrap_err();
+
+        match error.downcast_ref::<shellfn::Error<std::convert::Infallible>>() {
+            Some(shellfn::Error::NonUtf8Stdout(e)) => assert_eq!(b"*** \xff", e.as_bytes()),
+            e => panic!("unexpected error {:?}", e),
+        }
+    }
+}
+
+mod reports_progress {
+    use super::*;
+    use shellfn::Progress;
+    use std::sync::{Arc, Mutex};
+
+    #[shell]
+    fn migrate(#[progress] progress: impl FnMut(Progress) + Send + 'static) -> u32 {
+        r#"
+        shellfn_progress 1 2 users
+        echo not a progress line >&$SHELLFN_PROGRESS_FD
+        shellfn_progress 2 2
+        echo -n 42
+        "#
+    }
+
+    #[shell]
+    fn copy(
+        files: u32,
+        #[progress] progress: impl FnMut(Progress) + Send + 'static,
+    ) -> impl Iterator<Item = u32> {
+        r#"
+        for i in $(seq $FILES); do
+            shellfn_progress $i $FILES copying file $i
+            echo $i
+        done
+        "#
+    }
+
+    #[shell(cmd = "sh -c", result_fd = 3)]
+    fn migrate_with_sh(#[progress] progress: impl FnMut(Progress) + Send + 'static) -> Vec<u32> {
+        r#"
+        shellfn_progress 1 2 users
+        echo 1 >&3
+        shellfn_progress 2 2
+        echo 2 >&3
+        "#
+    }
+
+    #[shell(cmd = "sh -c", result_fd = 3)]
+    fn progress_fd(#[progress] progress: impl FnMut(Progress) + Send + 'static) -> u32 {
+        r#"echo -n $SHELLFN_PROGRESS_FD >&3"#
+    }
+
+    #[shell(cmd = "python -c")]
+    fn train(#[progress] progress: impl FnMut(Progress) + Send + 'static) -> u32 {
+        r#"
+import os, sys
+with os.fdopen(int(os.environ["SHELLFN_PROGRESS_FD"]), "w") as progress:
+    progress.write("1 1 epoch\n")
+sys.stdout.write("42")
+        "#
+    }
+
+    fn collector() -> (
+        Arc<Mutex<Vec<Progress>>>,
+        impl FnMut(Progress) + Send + 'static,
+    ) {
+        let updates = Arc::new(Mutex::new(Vec::new()));
+        let sink = updates.clone();
+
+        (updates, move |progress| sink.lock().unwrap().push(progress))
+    }
+
+    fn progress(done: u64, total: u64, message: Option<&str>) -> Progress {
+        Progress {
+            done,
+            total,
+            message: message.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn reports_progress_of_value() {
+        let (updates, callback) = collector();
+
+        assert_eq!(42, migrate(callback));
+        assert_eq!(
+            vec![progress(1, 2, Some("users")), progress(2, 2, None)],
+            *updates.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn keeps_progress_apart_from_result_fd() {
+        let (updates, callback) = collector();
+
+        assert_eq!(vec![1, 2], migrate_with_sh(callback));
+        assert_eq!(
+            vec![progress(1, 2, Some("users")), progress(2, 2, None)],
+            *updates.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn passes_progress_as_lowest_free_fd() {
+        assert_eq!(4, progress_fd(|_| ()));
+    }
+
+    #[test]
+    fn reports_progress_from_other_interpreter() {
+        let (updates, callback) = collector();
+
+        assert_eq!(42, train(callback));
+        assert_eq!(
+            vec![progress(1, 1, Some("epoch"))],
+            *updates.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn reports_progress_of_iterator() {
+        let (updates, callback) = collector();
+
+        assert_eq!(vec![1, 2], copy(2, callback).collect::<Vec<_>>());
+        assert_eq!(
+            vec![
+                progress(1, 2, Some("copying file 1")),
+                progress(2, 2, Some("copying file 2"))
+            ],
+            *updates.lock().unwrap()
+        );
+    }
+}
+
+mod yields_shell_events {
+    use super::*;
+    use shellfn::ShellEvent;
+    use std::num::ParseIntError;
+
+    #[shell]
+    fn build() -> impl Iterator<Item = ShellEvent<u32>> {
+        "echo 1; echo warning >&2; echo 2; echo error >&2; exit 3"
+    }
+
+    #[shell]
+    fn ordered() -> impl Iterator<Item = ShellEvent<String>> {
+        "echo first; sleep 0.1; echo second >&2; sleep 0.1; echo third"
+    }
+
+    #[shell]
+    fn fallible() -> Result<
+        impl Iterator<Item = Result<ShellEvent<u32>, shellfn::Error<ParseIntError>>>,
+        Box<dyn StdError>,
+    > {
+        "echo 1; echo x"
+    }
+
+    #[shell]
+    fn columns() -> Box<dyn Iterator<Item = ShellEvent<(String, u32)>>> {
+        "echo a 1; echo b 2"
+    }
+
+    fn exit_code<T>(event: Option<&ShellEvent<T>>) -> Option<i32> {
+        match event {
+            Some(ShellEvent::Exit(status)) => status.code(),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn tells_streams_apart() {
+        let events = build().collect::<Vec<_>>();
+        let stdout = events
+            .iter()
+            .filter_map(|event| match event {
+                ShellEvent::Stdout(item) => Some(*item),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let stderr = events
+            .iter()
+            .filter_map(|event| match event {
+                ShellEvent::Stderr(line) => Some(line.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec![1, 2], stdout);
+        assert_eq!(vec!["warning", "error"], stderr);
+        assert_eq!(Some(3), exit_code(events.last()));
+    }
+
+    #[test]
+    fn keeps_arrival_order() {
+        let events = ordered().collect::<Vec<_>>();
+
+        assert_eq!(
+            &[
+                ShellEvent::Stdout("first".to_string()),
+                ShellEvent::Stderr("second".to_string()),
+                ShellEvent::Stdout("third".to_string()),
+            ],
+            &events[..3]
+        );
+        assert_eq!(Some(0), exit_code(events.last()));
+    }
+
+    #[test]
+    fn yields_parsing_errors() {
+        let events = fallible().unwrap().collect::<Vec<_>>();
+
+        assert!(matches!(events[0], Ok(ShellEvent::Stdout(1))));
+        assert!(matches!(events[1], Err(shellfn::Error::ParsingError(_))));
+        assert!(matches!(events[2], Ok(ShellEvent::Exit(status)) if status.success()));
+    }
+
+    #[test]
+    fn parses_tuples() {
+        let items = columns()
+            .filter_map(|event| match event {
+                ShellEvent::Stdout(item) => Some(item),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec![("a".to_string(), 1), ("b".to_string(), 2)], items)
+    }
+}
+
+#[cfg(feature = "serde")]
+mod parses_json {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Package {
+        name:    String,
+        version: u32,
+    }
+
+    #[shell(format = "json")]
+    fn package(json: &str) -> Result<Package, BoxedError> {
+        r#"
+        printenv JSON
+        "#
+    }
+
+    #[shell(format = "json")]
+    fn packages() -> Vec<Package> {
+        r#"
+        echo '[{"name": "foo", "version": 1}, {"name": "bar", "version": 2}]'
+        "#
+    }
+
+    #[test]
+    fn deserializes_single_value() {
+        assert_eq!(
+            Package {
+                name:    "foo".to_string(),
+                version: 1,
+            },
+            package(r#"{"name": "foo", "version": 1}"#).unwrap()
+        )
+    }
+
+    #[test]
+    fn deserializes_vec_as_single_value() {
+        assert_eq!(
+            vec!["foo", "bar"],
+            packages().into_iter().map(|p| p.name).collect::<Vec<_>>()
+        )
+    }
+
+    #[test]
+    fn returns_error_with_line_and_column() {
+        let err = package("{\n  \"name\": 42\n}").unwrap_err();
+
+        match err.downcast_ref::<shellfn::Error<shellfn::NeverError>>() {
+            Some(shellfn::Error::JsonParsingError { line, column, .. }) => {
+                assert_eq!((2, 12), (*line, *column))
+            }
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+mod parses_json_lines {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Event {
+        id: u32,
+    }
+
+    #[shell(format = "jsonl")]
+    fn events() -> impl Iterator<Item = Result<Event, BoxedError>> {
+        r#"
+        echo '{"id": 1}'
+        echo '{"id": "two"}'
+        echo '{"id": 3}'
+        "#
+    }
+
+    #[shell(format = "jsonl")]
+    fn events_vec() -> Result<Vec<Event>, BoxedError> {
+        r#"
+        echo '{"id": 1}'
+        echo '{"id": 2}'
+        "#
+    }
+
+    #[test]
+    fn deserializes_every_line() {
+        assert_eq!(
+            vec![Event { id: 1 }, Event { id: 2 }],
+            events_vec().unwrap()
+        )
+    }
+
+    #[shell(format = "jsonl", skip_empty, skip_comments = "#")]
+    fn commented_events() -> impl Iterator<Item = Result<Event, BoxedError>> {
+        r#"
+        echo '# events'
+        echo '{"id": 1}'
+        echo
+        echo '{"id": "two"}'
+        "#
+    }
+
+    #[shell(format = "jsonl", separator = "paragraph")]
+    fn paragraph_events() -> impl Iterator<Item = Result<Event, BoxedError>> {
+        r#"
+        printf '{\n"id": 1\n}\n\n'
+        printf '{\n"id":\n"two"\n}\n'
+        "#
+    }
+
+    fn error_line(item: &Result<Event, BoxedError>) -> usize {
+        match item
+            .as_ref()
+            .unwrap_err()
+            .downcast_ref::<shellfn::Error<shellfn::NeverError>>()
+        {
+            Some(shellfn::Error::JsonParsingError { line, .. }) => *line,
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+
+    #[test]
+    fn counts_skipped_lines_in_line_number() {
+        let actual = commented_events().collect::<Vec<_>>();
+
+        assert_eq!(2, actual.len());
+        assert_eq!(4, error_line(&actual[1]));
+    }
+
+    #[test]
+    fn reports_line_number_within_paragraph() {
+        let actual = paragraph_events().collect::<Vec<_>>();
+
+        assert_eq!(2, actual.len());
+        assert_eq!(Event { id: 1 }, *actual[0].as_ref().unwrap());
+        assert_eq!(7, error_line(&actual[1]));
+    }
+
+    #[test]
+    fn yields_item_error_with_line_number() {
+        let actual = events().collect::<Vec<_>>();
+
+        assert_eq!(3, actual.len());
+        assert_eq!(Event { id: 1 }, *actual[0].as_ref().unwrap());
+        assert_eq!(Event { id: 3 }, *actual[2].as_ref().unwrap());
+
+        match actual[1]
+            .as_ref()
+            .unwrap_err()
+            .downcast_ref::<shellfn::Error<shellfn::NeverError>>()
+        {
+            Some(shellfn::Error::JsonParsingError { line, .. }) => assert_eq!(2, *line),
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+}
+
+#[cfg(feature = "csv")]
+mod parses_csv {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct User {
+        name: String,
+        uid:  u32,
+    }
+
+    #[shell(format = "csv")]
+    fn users() -> Result<Vec<User>, BoxedError> {
+        r#"
+        echo 'uid,name'
+        echo '0,root'
+        echo '1000,"Doe, John"'
+        "#
+    }
+
+    #[shell(format = "csv")]
+    fn multiline() -> Result<Vec<User>, BoxedError> {
+        r#"
+        echo 'name,uid'
+        printf '"first\nsecond",1\n'
+        "#
+    }
+
+    #[shell(format = "tsv")]
+    fn users_tsv() -> Result<Vec<User>, BoxedError> {
+        r#"
+        printf 'name\tuid\n'
+        printf 'root\t0\n'
+        "#
+    }
+
+    #[shell(format = "csv", no_headers)]
+    fn users_without_headers() -> Result<Vec<(String, u32)>, BoxedError> {
+        r#"
+        echo 'root,0'
+        echo 'nobody,65534'
+        "#
+    }
+
+    #[shell(format = "csv")]
+    fn users_iter() -> impl Iterator<Item = Result<User, BoxedError>> {
+        r#"
+        echo 'name,uid'
+        echo 'root,0'
+        echo 'nobody,unknown'
+        echo 'daemon,1'
+        "#
+    }
+
+    #[test]
+    fn maps_columns_by_header() {
+        assert_eq!(
+            vec![
+                User {
+                    name: "root".to_string(),
+                    uid:  0,
+                },
+                User {
+                    name: "Doe, John".to_string(),
+                    uid:  1000,
+                },
+            ],
+            users().unwrap()
+        )
+    }
+
+    #[test]
+    fn reads_quoted_fields_spanning_lines() {
+        assert_eq!("first\nsecond", multiline().unwrap()[0].name)
+    }
+
+    #[test]
+    fn reads_tab_separated_values() {
+        assert_eq!(
+            vec![User {
+                name: "root".to_string(),
+                uid:  0,
+            }],
+            users_tsv().unwrap()
+        )
+    }
+
+    #[test]
+    fn maps_columns_by_position_without_headers() {
+        assert_eq!(
+            vec![("root".to_string(), 0), ("nobody".to_string(), 65534)],
+            users_without_headers().unwrap()
+        )
+    }
+
+    #[test]
+    fn yields_item_error_for_invalid_row() {
+        let actual = users_iter().collect::<Vec<_>>();
+
+        assert_eq!(3, actual.len());
+        assert_eq!("root", actual[0].as_ref().unwrap().name);
+        assert!(actual[1].is_err());
+        assert_eq!("daemon", actual[2].as_ref().unwrap().name);
+    }
+}
+
+#[cfg(feature = "csv")]
+mod parses_table {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Disk {
+        #[serde(rename = "Filesystem")]
+        filesystem: String,
+        #[serde(rename = "Size")]
+        size:       String,
+        #[serde(rename = "Use%")]
+        usage:      String,
+        #[serde(rename = "Mounted on")]
+        mounted_on: String,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Process {
+        #[serde(rename = "PID")]
+        pid:     u32,
+        #[serde(rename = "COMMAND")]
+        command: String,
+    }
+
+    #[shell(format = "table")]
+    fn disks() -> Result<Vec<Disk>, BoxedError> {
+        r#"
+        echo 'Filesystem      Size  Used Avail Use% Mounted on'
+        echo '/dev/sda1        20G  5.1G   14G  27% /'
+        echo 'my disk        1000G   50G  950G   5% /mnt/backup'
+        "#
+    }
+
+    #[shell(format = "table")]
+    fn processes() -> impl Iterator<Item = Result<Process, BoxedError>> {
+        r#"
+        echo 'USER   PID COMMAND'
+        echo 'root     1 /sbin/init splash'
+        echo 'user    42 cargo test --workspace'
+        echo 'user     - sleep 1'
+        "#
+    }
+
+    #[test]
+    fn maps_columns_by_header() {
+        assert_eq!(
+            vec![
+                Disk {
+                    filesystem: "/dev/sda1".to_string(),
+                    size:       "20G".to_string(),
+                    usage:      "27%".to_string(),
+                    mounted_on: "/".to_string(),
+                },
+                Disk {
+                    filesystem: "my disk".to_string(),
+                    size:       "1000G".to_string(),
+                    usage:      "5%".to_string(),
+                    mounted_on: "/mnt/backup".to_string(),
+                },
+            ],
+            disks().unwrap()
+        )
+    }
+
+    #[test]
+    fn extends_last_column_to_end_of_line() {
+        let actual = processes().collect::<Vec<_>>();
+
+        assert_eq!(
+            Process {
+                pid:     1,
+                command: "/sbin/init splash".to_string(),
+            },
+            *actual[0].as_ref().unwrap()
+        );
+        assert_eq!(
+            "cargo test --workspace",
+            actual[1].as_ref().unwrap().command
+        );
+    }
+
+    #[test]
+    fn yields_item_error_with_row_number() {
+        let actual = processes().collect::<Vec<_>>();
+
+        match actual[2]
+            .as_ref()
+            .unwrap_err()
+            .downcast_ref::<shellfn::Error<shellfn::TableError>>()
+        {
+            Some(shellfn::Error::ParsingError(shellfn::TableError::InvalidRow { row, .. })) => {
+                assert_eq!(3, *row)
+            }
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+}
+
 mod analyzes_return_type {
     use super::*;
 
@@ -329,6 +2853,92 @@ mod analyzes_return_type {
         }
     }
 
+    mod when_fn_returns_bytes {
+        use super::*;
+
+        mod and_it_is_not_wrapped_with_result {
+            use super::*;
+
+            #[shell]
+            fn script(exit_code: u32) -> Vec<u8> {
+                r#"
+                printf 'a\x00\xff\n'
+                exit $EXIT_CODE
+                "#
+            }
+
+            #[shell]
+            fn bytes_script() -> bytes::Bytes {
+                r#"
+                printf '\xfe\xff'
+                "#
+            }
+
+            #[shell(cmd = "dummy_invalid_command_123")]
+            fn invalid_script() -> Vec<u8> {
+                r#"
+                invalid script
+                "#
+            }
+
+            #[test]
+            fn returns_raw_output_when_script_ends_with_success() {
+                assert_eq!(vec![b'a', 0, 255, b'\n'], script(0))
+            }
+
+            #[test]
+            fn returns_raw_output_as_bytes() {
+                assert_eq!(bytes::Bytes::from_static(&[254, 255]), bytes_script())
+            }
+
+            #[test]
+            #[should_panic]
+            fn panics_when_script_ends_with_failure() {
+                let _ = script(1);
+            }
+
+            #[test]
+            #[should_panic]
+            fn panics_when_script_is_invalid() {
+                let _ = invalid_script();
+            }
+        }
+
+        mod and_it_is_wrapped_with_result {
+            use super::*;
+
+            #[shell]
+            fn script(exit_code: u32) -> Result<Vec<u8>, BoxedError> {
+                r#"
+                printf 'a\x00\xff\n'
+                exit $EXIT_CODE
+                "#
+            }
+
+            #[shell(cmd = "dummy_invalid_command_123")]
+            fn invalid_script() -> Result<Vec<u8>, BoxedError> {
+                r#"
+                invalid script
+                "#
+            }
+
+            #[test]
+            fn returns_raw_output_when_script_ends_with_success() {
+                assert_eq!(vec![b'a', 0, 255, b'\n'], script(0).unwrap())
+            }
+
+            #[test]
+            fn returns_error_when_script_ends_with_failure() {
+                assert!(script(1).is_err())
+            }
+
+            #[test]
+            fn returns_error_when_script_is_invalid() {
+                assert!(invalid_script().is_err())
+            }
+        }
+    }
+
     mod when_fn_returns_iterator {
         use super::*;
 